@@ -0,0 +1,179 @@
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+use crate::password::Password;
+
+#[derive(Debug, Error)]
+pub enum AccountError {
+    #[error("there has to be at least one account")]
+    NoAccounts,
+
+    #[error("the account {0} has an empty password")]
+    EmptyPassword(String),
+
+    #[error("the account name {0} is used more than once")]
+    DuplicateName(String),
+
+    #[error("the account name {0} can't have a : in it")]
+    InvalidName(String),
+
+    #[error("{0} isn't a role; expected owner, moderator or viewer")]
+    InvalidRole(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Role {
+    /// can run anything by default
+    Owner,
+    /// can run a few moderation commands by default
+    Moderator,
+    /// can only read the console with `dumpconsole` and `rcon_subscribe` by default
+    Viewer,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Owner => "owner",
+            Role::Moderator => "moderator",
+            Role::Viewer => "viewer",
+        })
+    }
+}
+
+impl FromStr for Role {
+    type Err = AccountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "owner" => Ok(Role::Owner),
+            "moderator" => Ok(Role::Moderator),
+            "viewer" => Ok(Role::Viewer),
+            _ => Err(AccountError::InvalidRole(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Role {
+    type Error = AccountError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// a named credential
+///
+/// with more than one account clients log in with `name:password` as the AUTH body
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    pub name: String,
+    pub password: Password,
+    pub role: Role,
+}
+
+impl Account {
+    /// strings are taken as plain text; parse a [`Password`] to allow hashes
+    pub fn new(name: impl Into<String>, password: impl Into<Password>, role: Role) -> Self {
+        Self {
+            name: name.into(),
+            password: password.into(),
+            role,
+        }
+    }
+
+    fn identity(&self) -> Identity {
+        Identity {
+            name: self.name.clone(),
+            role: self.role,
+        }
+    }
+}
+
+/// who a session authenticated as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    pub role: Role,
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.role)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Accounts {
+    accounts: Vec<Account>,
+    /// made from a lone password so the whole AUTH body may be just that
+    bare_password: bool,
+}
+
+impl Accounts {
+    pub fn new(accounts: Vec<Account>) -> Result<Self, AccountError> {
+        if accounts.is_empty() {
+            Err(AccountError::NoAccounts)?
+        }
+
+        for (i, account) in accounts.iter().enumerate() {
+            if account.password.is_empty() {
+                Err(AccountError::EmptyPassword(account.name.clone()))?
+            }
+            if account.name.contains(':') {
+                Err(AccountError::InvalidName(account.name.clone()))?
+            }
+            if accounts[..i].iter().any(|other| other.name == account.name) {
+                Err(AccountError::DuplicateName(account.name.clone()))?
+            }
+        }
+
+        Ok(Self {
+            accounts,
+            bare_password: false,
+        })
+    }
+
+    /// a single owner account for the old `-rcon_password` setup
+    pub fn from_password(password: impl Into<Password>) -> Self {
+        Self {
+            accounts: vec![Account::new("admin", password, Role::Owner)],
+            bare_password: true,
+        }
+    }
+
+    /// `name:password`; an old `-rcon_password` setup also takes just the password
+    ///
+    /// at most two hashes are checked since a hash takes a while and this runs on the frame
+    pub fn authenticate(&self, attempt: &str) -> Option<Identity> {
+        let named = attempt.split_once(':').and_then(|(name, password)| {
+            self.accounts
+                .iter()
+                .find(|account| account.name == name)
+                .map(|account| (account, password))
+        });
+
+        match named {
+            Some((account, password)) if account.password.verify(password) => {
+                return Some(account.identity())
+            }
+            _ if self.bare_password => {}
+            Some(_) => return None,
+            None => {
+                // a hash is checked anyway so how long it takes doesn't give away which names exist
+                _ = self.accounts[0].password.verify(attempt);
+                return None;
+            }
+        }
+
+        let account = &self.accounts[0];
+        account.password.verify(attempt).then(|| account.identity())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.iter()
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::cidr::{parse_cidr_list, CidrError, IpCidr};
+
+/// northstar reads extra startup args from these next to the launcher
+pub const DEDICATED_ARGS_FILE: &str = "ns_startup_args_dedi.txt";
+pub const CLIENT_ARGS_FILE: &str = "ns_startup_args.txt";
+
+const VALID_RCON_ARGS: [&str; 6] = [
+    "rcon_ip_port",
+    "rcon_password",
+    "rcon_password_file",
+    "rcon_allow",
+    "rcon_deny",
+    "rcon_config",
+];
+
+#[derive(Debug, Error)]
+pub enum ArgError {
+    #[error("{0} needs a value; use {0}=<value> if the value starts with - or +")]
+    MissingValue(String),
+
+    #[error("{flag}: {err}")]
+    InvalidCidr { flag: String, err: CidrError },
+
+    #[error("{0} has a quote that's never closed")]
+    UnterminatedQuote(String),
+
+    #[error("couldn't read {path}: {err}")]
+    Unreadable { path: PathBuf, err: io::Error },
+}
+
+/// the rcon settings given on the command line; these win over the config file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RconArgs {
+    pub ip_port: Option<String>,
+    pub password: Option<String>,
+    pub password_file: Option<PathBuf>,
+    pub allow: Option<Vec<IpCidr>>,
+    pub deny: Option<Vec<IpCidr>>,
+    pub config: Option<PathBuf>,
+}
+
+impl RconArgs {
+    /// the startup args file then the real command line, so the command line wins
+    pub fn from_env() -> Result<Self, ArgError> {
+        let args = std::env::args().collect::<Vec<String>>();
+        let args_file = if args.iter().any(|arg| arg == "-dedicated") {
+            DEDICATED_ARGS_FILE
+        } else {
+            CLIENT_ARGS_FILE
+        };
+
+        Self::parse(
+            read_args_file(Path::new(args_file))?
+                .into_iter()
+                .chain(args.into_iter().skip(1)),
+        )
+    }
+
+    /// picks the rcon flags out of a command line and skips everything else
+    ///
+    /// flags start with `-` or `+` and take their value as `-flag value` or `-flag=value`.
+    /// the last one wins when a flag is given more than once
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgError> {
+        let mut values = HashMap::<&str, String>::new();
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix(['-', '+']) else {
+                continue;
+            };
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+
+            // `+name` is a convar set for the engine so only `-rcon_*` can be a typo of ours
+            let Some(name) = VALID_RCON_ARGS.iter().find(|valid| **valid == name) else {
+                if name.starts_with("rcon_") && arg.starts_with('-') {
+                    log::warn!(
+                        "ignoring {arg} since it isn't an rcon flag; expected one of -{}",
+                        VALID_RCON_ARGS.join(", -")
+                    );
+                }
+                continue;
+            };
+
+            let value = match inline_value {
+                Some(value) => value,
+                None => args
+                    .next_if(|value| !is_flag(value))
+                    .ok_or_else(|| ArgError::MissingValue(format!("-{name}")))?,
+            };
+            if value.is_empty() {
+                Err(ArgError::MissingValue(format!("-{name}")))?
+            }
+
+            if let Some(old) = values.insert(name, value) {
+                if values[name] != old {
+                    log::warn!("-{name} was given more than once; using the last one");
+                }
+            }
+        }
+
+        let cidrs = |name: &str| {
+            values
+                .get(name)
+                .map(|list| parse_cidr_list(list))
+                .transpose()
+                .map_err(|err| ArgError::InvalidCidr {
+                    flag: format!("-{name}"),
+                    err,
+                })
+        };
+
+        Ok(Self {
+            allow: cidrs("rcon_allow")?,
+            deny: cidrs("rcon_deny")?,
+            ip_port: values.remove("rcon_ip_port"),
+            password: values.remove("rcon_password"),
+            password_file: values.remove("rcon_password_file").map(PathBuf::from),
+            config: values.remove("rcon_config").map(PathBuf::from),
+        })
+    }
+}
+
+/// `-5` is a value but `-flag` and `+flag` aren't
+fn is_flag(arg: &str) -> bool {
+    arg.strip_prefix(['-', '+'])
+        .and_then(|flag| flag.chars().next())
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+}
+
+/// the args in a startup args file or nothing if there isn't one
+pub fn read_args_file(path: &Path) -> Result<Vec<String>, ArgError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => split_args(&contents)
+            .ok_or_else(|| ArgError::UnterminatedQuote(path.display().to_string())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(ArgError::Unreadable {
+            path: path.to_path_buf(),
+            err,
+        }),
+    }
+}
+
+/// splits on whitespace outside of double quotes and drops the quotes; `None` if a quote is left open
+pub fn split_args(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg = None::<String>;
+    let mut in_quotes = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                _ = arg.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !in_quotes => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+
+    (!in_quotes).then_some(args)
+}
@@ -99,7 +99,7 @@ pub struct IConsoleDisplayFuncVtable {
 
 offset_functions! {
     ENGINE_FUNCTIONS + EngineFunctions for WhichDll::Engine => {
-        ccommand_tokenize = unsafe extern "C" fn(&mut Option<CCommand>, *const c_char, CmdSource) -> bool where offset(0x418380);
+        ccommand_tokenize = unsafe extern "C" fn(*mut CCommand, *const c_char, CmdSource) -> bool where offset(0x418380);
         cbuf_add_text_type = unsafe extern "C" fn(EcommandTarget, *const c_char, CmdSource) where offset(0x1203B0);
         cbuf_execute = unsafe extern "C" fn() where offset(0x1204B0);
         cbuf_get_current_player = unsafe extern "C" fn() -> EcommandTarget where offset(0x120630);
@@ -0,0 +1,124 @@
+use serde::Deserialize;
+use std::{fmt, net::IpAddr, str::FromStr};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CidrError {
+    #[error("{0} isn't an ip address")]
+    InvalidAddress(String),
+
+    #[error("{0} isn't a valid prefix length")]
+    InvalidPrefix(String),
+
+    #[error("a prefix of {prefix} is too long for {addr}; the max is {max}")]
+    PrefixTooLong { addr: IpAddr, prefix: u8, max: u8 },
+}
+
+/// an ipv4 or ipv6 range like `10.8.0.0/24` or `fd00::/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpCidr {
+    /// host bits in `addr` are cleared
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, CidrError> {
+        let max = max_prefix(addr);
+        if prefix > max {
+            Err(CidrError::PrefixTooLong { addr, prefix, max })?
+        }
+
+        Ok(Self {
+            addr: mask(addr, prefix),
+            prefix,
+        })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+
+        max_prefix(ip) == max_prefix(self.addr) && mask(ip, self.prefix) == self.addr
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = CidrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = s.split_once('/').unwrap_or((s, ""));
+
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| CidrError::InvalidAddress(addr.to_string()))?
+            .to_canonical();
+
+        let prefix = match prefix {
+            "" => max_prefix(addr),
+            prefix => prefix
+                .parse()
+                .map_err(|_| CidrError::InvalidPrefix(prefix.to_string()))?,
+        };
+
+        IpCidr::new(addr, prefix)
+    }
+}
+
+impl TryFrom<String> for IpCidr {
+    type Error = CidrError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+fn max_prefix(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((addr.to_bits() & mask).into())
+        }
+        IpAddr::V6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((addr.to_bits() & mask).into())
+        }
+    }
+}
+
+/// decides which peers may connect; deny entries win and an empty allow list lets everyone else in
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IpFilter {
+    pub allow: Vec<IpCidr>,
+    pub deny: Vec<IpCidr>,
+}
+
+impl IpFilter {
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        !self.deny.iter().any(|cidr| cidr.contains(ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip)))
+    }
+}
+
+/// parses a comma separated list like `10.0.0.0/8, ::1`
+pub fn parse_cidr_list(list: &str) -> Result<Vec<IpCidr>, CidrError> {
+    list.split(',')
+        .filter(|cidr| !cidr.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
@@ -0,0 +1,78 @@
+/// characters the engine's tokenizer turns into tokens of their own
+const BREAK_SET: &[char] = &['{', '}', '(', ')', '\'', ':'];
+
+/// splits a command line on `;` and newlines outside of quotes like the command buffer does
+pub fn split_commands(command_line: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (i, c) in command_line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                commands.push(&command_line[start..i]);
+                start = i + 1;
+            }
+            '\n' => {
+                commands.push(&command_line[start..i]);
+                start = i + 1;
+                in_quotes = false;
+            }
+            _ => {}
+        }
+    }
+    commands.push(&command_line[start..]);
+
+    commands
+        .into_iter()
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+/// a port of `CCommand::Tokenize` for when the engine isn't there
+///
+/// quoted strings are one argument and the break set characters are always their own argument
+pub fn tokenize(command: &str) -> Vec<String> {
+    let mut argv = Vec::new();
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '"' => {
+                let mut arg = String::new();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    arg.push(c);
+                }
+                argv.push(arg);
+            }
+            c if BREAK_SET.contains(&c) => argv.push(c.to_string()),
+            c => {
+                let mut arg = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '"' || BREAK_SET.contains(&c) {
+                        break;
+                    }
+                    arg.push(c);
+                    _ = chars.next();
+                }
+                argv.push(arg);
+            }
+        }
+    }
+
+    argv
+}
+
+/// formats argv with every argument quoted so it's clear where each one starts and ends
+pub fn format_argv(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| format!("\"{arg}\""))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,497 @@
+use log::LevelFilter;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use thiserror::Error;
+
+use crate::{
+    accounts::{Account, AccountError, Accounts, Role},
+    args::RconArgs,
+    cidr::IpFilter,
+    console::ConsoleSource,
+    executor::CommandExecutor,
+    history::HistoryLimits,
+    packet::MAX_PACKET_SIZE,
+    password::{resolve_password, Password, PasswordError, PasswordSource, PASSWORD_ENV_VAR},
+    permissions::{Permissions, RoleRules},
+    rcon::{RconLimits, RconServer, MIN_WRITE_BUFFER},
+    throttle::AuthPolicy,
+};
+
+pub const CONFIG_FILE_NAME: &str = "r2rcon.toml";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("couldn't read {path}: {err}")]
+    Unreadable { path: PathBuf, err: io::Error },
+
+    #[error(transparent)]
+    Parse(#[from] toml::de::Error),
+
+    #[error("there's no address to listen on; set bind in the config or pass -rcon_ip_port")]
+    NoBindAddress,
+
+    #[error("there's no password; set password, password_file or accounts in the config, pass -rcon_password or -rcon_password_file or set {PASSWORD_ENV_VAR}")]
+    NoCredentials,
+
+    #[error("only one of password, password_file and accounts can be set")]
+    ConflictingCredentials,
+
+    #[error("the config has accounts but a password came from {0} too; drop one of them so the accounts' roles aren't lost")]
+    AccountsOverridden(PasswordSource),
+
+    #[error(transparent)]
+    Password(#[from] PasswordError),
+
+    #[error("accounts: {0}")]
+    Accounts(#[from] AccountError),
+
+    #[error("{0} has to be more than 0")]
+    Zero(&'static str),
+
+    #[error("{field} has to be at least {min} but got {value}")]
+    TooSmall {
+        field: &'static str,
+        min: usize,
+        value: usize,
+    },
+}
+
+/// `r2rcon.toml`; everything is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// addresses to listen on like `0.0.0.0:27015` or `[::]:27015`
+    pub bind: Vec<String>,
+    /// a single owner account with this password
+    pub password: Option<Password>,
+    /// relative to the config file when there is one
+    pub password_file: Option<PathBuf>,
+    pub accounts: Vec<Account>,
+    pub limits: LimitsConfig,
+    pub auth: AuthConfig,
+    pub ip_filter: IpFilter,
+    /// how much of the console `dumpconsole` can go back through
+    pub history: HistoryLimits,
+    /// replaces the default rules of the roles it mentions
+    pub permissions: HashMap<Role, RoleRules>,
+    pub logging: LoggingConfig,
+    pub capture: CaptureConfig,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_packet_size: usize,
+    pub max_write_buffer: usize,
+    pub max_connections: usize,
+    pub max_connections_per_ip: usize,
+    pub auth_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub response_frames: u32,
+    pub response_timeout_ms: u64,
+    pub response_quiet_frames: u32,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        let limits = RconLimits::default();
+
+        Self {
+            max_packet_size: limits.max_packet_size,
+            max_write_buffer: limits.max_write_buffer,
+            max_connections: limits.max_connections,
+            max_connections_per_ip: limits.max_connections_per_ip,
+            auth_timeout_secs: limits.auth_timeout.as_secs(),
+            idle_timeout_secs: limits.idle_timeout.as_secs(),
+            response_frames: limits.response_frames,
+            response_timeout_ms: limits.response_timeout.as_millis() as u64,
+            response_quiet_frames: limits.response_quiet_frames,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub max_failures_per_connection: u32,
+    pub ban_threshold: u32,
+    pub ban_duration_secs: u64,
+    pub failure_window_secs: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        let policy = AuthPolicy::default();
+
+        Self {
+            base_delay_ms: policy.base_delay.as_millis() as u64,
+            max_delay_ms: policy.max_delay.as_millis() as u64,
+            max_failures_per_connection: policy.max_failures_per_connection,
+            ban_threshold: policy.ban_threshold,
+            ban_duration_secs: policy.ban_duration.as_secs(),
+            failure_window_secs: policy.failure_window.as_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub level: LevelFilter,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+        }
+    }
+}
+
+/// which engine hooks feed the console capture
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureConfig {
+    /// the dedicated server's console window
+    pub write_console: bool,
+    /// the in game console
+    pub print: bool,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            write_console: true,
+            print: true,
+        }
+    }
+}
+
+impl Config {
+    /// `-rcon_config` if it was given, otherwise `r2rcon.toml` next to the plugin if there is one
+    pub fn find(arg: Option<&Path>, plugin_dir: Option<&Path>) -> Option<PathBuf> {
+        arg.map(Path::to_path_buf).or_else(|| {
+            plugin_dir
+                .map(|dir| dir.join(CONFIG_FILE_NAME))
+                .filter(|path| path.is_file())
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|err| ConfigError::Unreadable {
+            path: path.to_path_buf(),
+            err,
+        })?;
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            ..Self::parse(&contents)?
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// checks the config and puts the command line and `env_password` over it
+    pub fn resolve(
+        self,
+        args: &RconArgs,
+        env_password: Option<String>,
+    ) -> Result<Settings, ConfigError> {
+        let bind = match &args.ip_port {
+            Some(ip_port) => vec![ip_port.clone()],
+            None => self.bind,
+        };
+        if bind.is_empty() {
+            Err(ConfigError::NoBindAddress)?
+        }
+
+        let in_file = [
+            self.password.is_some(),
+            self.password_file.is_some(),
+            !self.accounts.is_empty(),
+        ];
+        let password_file = self.password_file.map(|path| match &self.path {
+            Some(config) if path.is_relative() => {
+                config.parent().unwrap_or(Path::new("")).join(path)
+            }
+            _ => path,
+        });
+        let (accounts, password_source) = match resolve_password(
+            args.password_file.as_deref(),
+            env_password,
+            args.password.as_deref(),
+        )? {
+            // a leftover password in the environment shouldn't quietly turn every account into one owner
+            Some((_, source)) if !self.accounts.is_empty() => {
+                Err(ConfigError::AccountsOverridden(source))?
+            }
+            Some((password, source)) => (Accounts::from_password(password), Some(source)),
+            None if in_file.iter().filter(|set| **set).count() > 1 => {
+                Err(ConfigError::ConflictingCredentials)?
+            }
+            None => match (self.password, password_file) {
+                (Some(password), _) => (
+                    Accounts::from_password(password),
+                    Some(PasswordSource::Config),
+                ),
+                (_, Some(path)) => resolve_password(Some(&path), None, None)?
+                    .map(|(password, source)| (Accounts::from_password(password), Some(source)))
+                    .ok_or(ConfigError::NoCredentials)?,
+                _ if self.accounts.is_empty() => Err(ConfigError::NoCredentials)?,
+                _ => (Accounts::new(self.accounts)?, None),
+            },
+        };
+
+        let mut ip_filter = self.ip_filter;
+        if let Some(allow) = &args.allow {
+            ip_filter.allow = allow.clone();
+        }
+        if let Some(deny) = &args.deny {
+            ip_filter.deny = deny.clone();
+        }
+
+        let mut permissions = Permissions::default();
+        for (role, rules) in self.permissions {
+            permissions.set_rules(role, rules);
+        }
+
+        non_zero("history.max_lines", self.history.max_lines as u64)?;
+        non_zero("history.max_bytes", self.history.max_bytes as u64)?;
+
+        Ok(Settings {
+            path: self.path,
+            bind,
+            accounts,
+            password_source,
+            limits: self.limits.validate()?,
+            auth_policy: self.auth.validate()?,
+            ip_filter,
+            history: self.history,
+            permissions,
+            log_level: self.logging.level,
+            capture: self.capture,
+        })
+    }
+}
+
+impl LimitsConfig {
+    fn validate(self) -> Result<RconLimits, ConfigError> {
+        // anything smaller and a normal command or its reply can't fit so every client gets dropped
+        at_least(
+            "limits.max_packet_size",
+            self.max_packet_size,
+            MAX_PACKET_SIZE,
+        )?;
+        at_least(
+            "limits.max_write_buffer",
+            self.max_write_buffer,
+            MIN_WRITE_BUFFER,
+        )?;
+        non_zero("limits.max_connections", self.max_connections as u64)?;
+        non_zero(
+            "limits.max_connections_per_ip",
+            self.max_connections_per_ip as u64,
+        )?;
+        non_zero("limits.auth_timeout_secs", self.auth_timeout_secs)?;
+        non_zero("limits.idle_timeout_secs", self.idle_timeout_secs)?;
+
+        Ok(RconLimits {
+            max_packet_size: self.max_packet_size,
+            max_write_buffer: self.max_write_buffer,
+            max_connections: self.max_connections,
+            max_connections_per_ip: self.max_connections_per_ip,
+            auth_timeout: Duration::from_secs(self.auth_timeout_secs),
+            idle_timeout: Duration::from_secs(self.idle_timeout_secs),
+            response_frames: self.response_frames,
+            response_timeout: Duration::from_millis(self.response_timeout_ms),
+            response_quiet_frames: self.response_quiet_frames,
+        })
+    }
+}
+
+impl AuthConfig {
+    fn validate(self) -> Result<AuthPolicy, ConfigError> {
+        non_zero(
+            "auth.max_failures_per_connection",
+            self.max_failures_per_connection as u64,
+        )?;
+        non_zero("auth.ban_threshold", self.ban_threshold as u64)?;
+
+        Ok(AuthPolicy {
+            base_delay: Duration::from_millis(self.base_delay_ms),
+            max_delay: Duration::from_millis(self.max_delay_ms),
+            max_failures_per_connection: self.max_failures_per_connection,
+            ban_threshold: self.ban_threshold,
+            ban_duration: Duration::from_secs(self.ban_duration_secs),
+            failure_window: Duration::from_secs(self.failure_window_secs),
+        })
+    }
+}
+
+fn non_zero(field: &'static str, value: u64) -> Result<(), ConfigError> {
+    match value {
+        0 => Err(ConfigError::Zero(field)),
+        _ => Ok(()),
+    }
+}
+
+fn at_least(field: &'static str, value: usize, min: usize) -> Result<(), ConfigError> {
+    match value < min {
+        true => Err(ConfigError::TooSmall { field, min, value }),
+        false => Ok(()),
+    }
+}
+
+/// the config everything ends up using
+#[derive(Debug)]
+pub struct Settings {
+    /// the config file it came from if there was one
+    pub path: Option<PathBuf>,
+    pub bind: Vec<String>,
+    pub accounts: Accounts,
+    /// where the password came from when there's a single one
+    pub password_source: Option<PasswordSource>,
+    pub limits: RconLimits,
+    pub auth_policy: AuthPolicy,
+    pub ip_filter: IpFilter,
+    pub history: HistoryLimits,
+    pub permissions: Permissions,
+    pub log_level: LevelFilter,
+    pub capture: CaptureConfig,
+}
+
+impl Settings {
+    pub fn start_server(
+        &self,
+        console: impl ConsoleSource + 'static,
+        executor: impl CommandExecutor + 'static,
+    ) -> Result<RconServer, io::Error> {
+        let mut server =
+            RconServer::try_new(&self.bind[0], self.accounts.clone(), console, executor)?;
+        for bind_ip in &self.bind[1..] {
+            server.listen(bind_ip)?;
+        }
+
+        server.set_limits(self.limits.clone());
+        server.set_auth_policy(self.auth_policy.clone());
+        server.set_ip_filter(self.ip_filter.clone());
+        server.set_history_limits(self.history);
+        server.set_permissions(self.permissions.clone());
+
+        Ok(server)
+    }
+}
+
+/// a summary for the log; never has a password in it
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => writeln!(f, "rcon config from {}", path.display())?,
+            None => writeln!(f, "rcon config from the command line")?,
+        }
+
+        writeln!(f, "  listening on {}", self.bind.join(", "))?;
+
+        write!(
+            f,
+            "  accounts: {}",
+            join(
+                self.accounts
+                    .iter()
+                    .map(|account| format!("{} ({})", account.name, account.role))
+            )
+        )?;
+        match &self.password_source {
+            Some(source) => writeln!(f, " with the password from {source}")?,
+            None => writeln!(f)?,
+        }
+
+        let limits = &self.limits;
+        writeln!(
+            f,
+            "  limits: {} connections, {} per ip, {} byte packets, {} byte write buffers, {:?} to authenticate, {:?} idle timeout, output waited on for {} frames or {:?} and until it's quiet for {} frames",
+            limits.max_connections,
+            limits.max_connections_per_ip,
+            limits.max_packet_size,
+            limits.max_write_buffer,
+            limits.auth_timeout,
+            limits.idle_timeout,
+            limits.response_frames,
+            limits.response_timeout,
+            limits.response_quiet_frames
+        )?;
+
+        let policy = &self.auth_policy;
+        writeln!(
+            f,
+            "  failed auths: {:?} up to {:?} delay, dropped after {} per connection, banned for {:?} after {} within {:?}",
+            policy.base_delay,
+            policy.max_delay,
+            policy.max_failures_per_connection,
+            policy.ban_duration,
+            policy.ban_threshold,
+            policy.failure_window
+        )?;
+
+        writeln!(
+            f,
+            "  ip filter: allow {}; deny {}",
+            or_if_empty(join(self.ip_filter.allow.iter()), "everyone"),
+            or_if_empty(join(self.ip_filter.deny.iter()), "no one")
+        )?;
+
+        writeln!(
+            f,
+            "  console history: {} lines or {} bytes",
+            self.history.max_lines, self.history.max_bytes
+        )?;
+
+        for role in [Role::Owner, Role::Moderator, Role::Viewer] {
+            let rules = self.permissions.rules(role).cloned().unwrap_or_default();
+            writeln!(
+                f,
+                "  {role} can run {}; except {}",
+                or_if_empty(join(rules.allow.iter()), "nothing"),
+                or_if_empty(join(rules.deny.iter()), "nothing")
+            )?;
+        }
+
+        writeln!(f, "  log level: {}", self.log_level)?;
+
+        let hooks = [
+            self.capture.write_console.then_some("WriteConsoleA"),
+            self.capture.print.then_some("Print"),
+        ];
+        write!(
+            f,
+            "  capturing the console from {}",
+            or_if_empty(join(hooks.into_iter().flatten()), "nothing")
+        )
+    }
+}
+
+fn join(items: impl Iterator<Item = impl fmt::Display>) -> String {
+    items
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn or_if_empty(list: String, empty: &str) -> String {
+    if list.is_empty() {
+        empty.to_string()
+    } else {
+        list
+    }
+}
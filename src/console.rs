@@ -1,45 +1,366 @@
-use std::sync::mpsc::Receiver;
+use regex::Regex;
+use std::{
+    borrow::Cow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
 
-use crate::rcon::MAX_CONTENT_SIZE;
+use crate::history::{ConsoleHistory, HistoryLimits};
+
+/// tags northstar puts in front of its log lines
+const SEVERITY_TAGS: [(&str, Severity); 7] = [
+    ("[error]", Severity::Error),
+    ("[critical]", Severity::Error),
+    ("[warning]", Severity::Warning),
+    ("[warn]", Severity::Warning),
+    ("[info]", Severity::Info),
+    ("[debug]", Severity::Debug),
+    ("[trace]", Severity::Debug),
+];
+
+/// where console output comes from; the engine hooks feed a channel
+pub trait ConsoleSource: Send {
+    /// the next captured line if there is one without waiting
+    fn next_line(&mut self) -> Option<String>;
+}
+
+impl ConsoleSource for Receiver<String> {
+    fn next_line(&mut self) -> Option<String> {
+        self.try_recv().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// guesses from the log tag or an `error:`/`warning:` prefix; anything else is info
+    pub fn of(line: &str) -> Self {
+        let line = line.to_ascii_lowercase();
+
+        SEVERITY_TAGS
+            .iter()
+            .find(|(tag, _)| line.contains(tag))
+            .map(|(_, severity)| *severity)
+            .unwrap_or_else(|| match line.trim_start() {
+                line if line.starts_with("error") => Severity::Error,
+                line if line.starts_with("warning") => Severity::Warning,
+                _ => Severity::Info,
+            })
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+impl FromStr for Severity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(Severity::Debug),
+            "info" => Ok(Severity::Info),
+            "warning" | "warn" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(()),
+        }
+    }
+}
+
+/// which console lines a subscription wants
+#[derive(Debug, Clone)]
+pub struct ConsoleFilter {
+    pub min_severity: Severity,
+    pub pattern: Option<Regex>,
+}
+
+impl ConsoleFilter {
+    /// `[severity] [regex]`; a first word that isn't a severity starts the regex
+    pub fn parse(args: &str) -> Result<Self, regex::Error> {
+        let args = args.trim();
+        let (first, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+
+        let (min_severity, pattern) = match first.parse() {
+            Ok(severity) => (severity, rest.trim()),
+            Err(()) => (Severity::Debug, args),
+        };
+
+        Ok(Self {
+            min_severity,
+            pattern: (!pattern.is_empty())
+                .then(|| Regex::new(pattern))
+                .transpose()?,
+        })
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        Severity::of(line) >= self.min_severity
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(line))
+    }
+}
+
+impl fmt::Display for ConsoleFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} and up", self.min_severity)?;
+        match &self.pattern {
+            Some(pattern) => write!(f, " matching {pattern}"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaptureState {
+    /// the begin sentinel didn't come through yet
+    Waiting,
+    Inside,
+    /// the command ran but things like changelevel keep printing for a while after
+    Trailing,
+}
+
+/// the output of one command, collected from the sentinel lines echoed around it
+///
+/// lines after the end sentinel only count while it's the one command waiting on more output
+struct Capture {
+    tag: String,
+    begin: String,
+    end: String,
+    state: CaptureState,
+    /// when it was given up on; only kept a while after so its sentinels don't end up in the history
+    abandoned: Option<Instant>,
+    output: String,
+    max_output: usize,
+    cut_off: bool,
+    /// whether it may keep collecting after its end sentinel; those lines could be anything in the console
+    trails: bool,
+}
+
+impl Capture {
+    fn push(&mut self, line: &str) {
+        match self.output.len() + line.len() > self.max_output {
+            true => self.cut_off = true,
+            false => self.output += line,
+        }
+    }
+}
 
 pub struct ConsoleAccess {
-    console_recv: Receiver<String>,
-    cmd_buffer: Vec<String>,
+    console_source: Box<dyn ConsoleSource>,
+    history: ConsoleHistory,
+    /// lines captured since the subscribers last got theirs
+    fresh_lines: Vec<String>,
+    captures: Vec<Capture>,
+    /// every tag starts with it; the key in it is random so nobody can echo a sentinel themselves
+    sentinel_prefix: String,
+    next_tag: u64,
 }
 
 impl ConsoleAccess {
-    pub fn new(recv: Receiver<String>) -> Self {
+    pub fn new(source: impl ConsoleSource + 'static) -> Self {
         Self {
-            console_recv: recv,
-            cmd_buffer: Vec::new(),
+            console_source: Box::new(source),
+            history: ConsoleHistory::new(HistoryLimits::default()),
+            fresh_lines: Vec::new(),
+            captures: Vec::new(),
+            sentinel_prefix: format!(
+                "r2rcon-{:016x}-",
+                RandomState::new().build_hasher().finish()
+            ),
+            next_tag: 0,
         }
     }
 
-    pub fn next_line(&self) -> Option<String> {
-        self.console_recv.try_recv().ok()
+    pub fn next_line(&mut self) -> Option<String> {
+        self.console_source.next_line()
     }
 
-    pub fn next_line_catpure(&mut self) -> Option<String> {
-        if let Some(line) = self.next_line() {
-            let line_size = line.len();
-            let mut buffer_size = 0;
+    /// takes in everything the console printed since the last time
+    ///
+    /// sentinel lines only move their capture along and never make it into the history, even once
+    /// their capture is gone
+    pub fn drain(&mut self) {
+        while let Some(line) = self.next_line() {
+            // the console hooks can hand over lines with colour codes around the text
+            let text = strip_ansi(&line);
 
-            for bline in self.cmd_buffer.drain(..).rev().collect::<Vec<String>>() {
-                buffer_size += bline.len();
-                if buffer_size + line_size > MAX_CONTENT_SIZE {
-                    break;
+            if let Some(capture) = self.captures.iter_mut().find(|c| text.contains(&c.begin)) {
+                capture.state = CaptureState::Inside;
+            } else if let Some(i) = self.captures.iter().position(|c| text.contains(&c.end)) {
+                if self.captures[i].abandoned.is_some() {
+                    _ = self.captures.remove(i);
+                } else {
+                    self.captures[i].state = CaptureState::Trailing;
                 }
-
-                self.cmd_buffer.insert(0, bline);
+            } else if text.contains(&self.sentinel_prefix) {
+                continue;
+            } else {
+                if let Some(i) = self.owner() {
+                    self.captures[i].push(&line);
+                }
+                self.record(line);
             }
-            self.cmd_buffer.push(line.clone());
+        }
+    }
+
+    /// the one capture a line that isn't a sentinel belongs to
+    ///
+    /// when it's not clear whose it is it goes to nobody so one session never gets another's output
+    fn owner(&self) -> Option<usize> {
+        let live = |state| {
+            self.captures
+                .iter()
+                .enumerate()
+                .filter(move |(_, c)| c.state == state && c.abandoned.is_none())
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+
+        match live(CaptureState::Inside)[..] {
+            [i] => Some(i),
+            [] => match live(CaptureState::Trailing)[..] {
+                [i] if self.captures[i].trails => Some(i),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// keeps a line for `dumpconsole` and the subscribers
+    pub fn record(&mut self, line: String) {
+        self.fresh_lines.push(line.clone());
+        _ = self.history.push(line);
+    }
+
+    /// a tag for `start_capture` that's never been used before
+    pub fn next_tag(&mut self, session: u64) -> String {
+        self.next_tag += 1;
+        format!("{}{session}-{}", self.sentinel_prefix, self.next_tag)
+    }
+
+    /// starts collecting the output of a command; returns the begin and end lines to echo around it
+    ///
+    /// anything past `max_output` bytes is left out of the capture. with `trails` it also gets what's
+    /// printed after the end sentinel while no other command is waiting
+    pub fn start_capture(
+        &mut self,
+        tag: &str,
+        max_output: usize,
+        trails: bool,
+    ) -> (String, String) {
+        let (begin, end) = (format!("{tag}-begin"), format!("{tag}-end"));
+        self.captures.push(Capture {
+            tag: tag.to_string(),
+            begin: begin.clone(),
+            end: end.clone(),
+            state: CaptureState::Waiting,
+            abandoned: None,
+            output: String::new(),
+            max_output,
+            cut_off: false,
+            trails,
+        });
+
+        (begin, end)
+    }
+
+    /// whether the end sentinel of a capture came through and how much output it has so far
+    pub fn capture_progress(&self, tag: &str) -> (bool, usize) {
+        self.captures
+            .iter()
+            .find(|capture| capture.tag == tag && capture.abandoned.is_none())
+            .map_or((true, 0), |capture| {
+                (
+                    capture.state == CaptureState::Trailing,
+                    capture.output.len(),
+                )
+            })
+    }
+
+    /// stops a capture and returns what it collected, done or not
+    pub fn finish_capture(&mut self, tag: &str, now: Instant) -> String {
+        let Some(i) = self
+            .captures
+            .iter()
+            .position(|capture| capture.tag == tag && capture.abandoned.is_none())
+        else {
+            return String::new();
+        };
 
-            return Some(line);
+        let capture = &mut self.captures[i];
+        let mut output = std::mem::take(&mut capture.output);
+        if capture.cut_off {
+            output += &format!("... cut off after {} bytes\n", capture.max_output);
         }
-        None
+
+        match capture.state {
+            CaptureState::Trailing => _ = self.captures.remove(i),
+            _ => capture.abandoned = Some(now),
+        }
+
+        output
     }
 
-    pub fn get_last_console_output(&self) -> &[String] {
-        &self.cmd_buffer
+    /// forgets captures given up on longer than `keep` ago whose end sentinel never showed up
+    pub fn prune_captures(&mut self, now: Instant, keep: Duration) {
+        self.captures.retain(|capture| {
+            capture
+                .abandoned
+                .is_none_or(|abandoned| now.duration_since(abandoned) < keep)
+        });
     }
+
+    pub fn history(&self) -> &ConsoleHistory {
+        &self.history
+    }
+
+    pub fn set_history_limits(&mut self, limits: HistoryLimits) {
+        self.history.set_limits(limits);
+    }
+
+    pub fn take_fresh_lines(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.fresh_lines)
+    }
+}
+
+/// drops ansi escape sequences like `\x1b[0m`
+fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return Cow::Borrowed(line);
+    }
+
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // csi sequences run until a byte in @..~
+            '\x1b' if chars.clone().next() == Some('[') => {
+                _ = chars.next();
+                _ = chars.by_ref().find(|c| ('@'..='~').contains(c));
+            }
+            '\x1b' => _ = chars.next(),
+            c => text.push(c),
+        }
+    }
+
+    Cow::Owned(text)
 }
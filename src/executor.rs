@@ -0,0 +1,73 @@
+#[cfg(feature = "engine")]
+use rrplug::{
+    bindings::cvar::command::CCommand,
+    mid::utils::{to_cstring, try_cstring},
+};
+#[cfg(feature = "engine")]
+use std::{ffi::CStr, mem::MaybeUninit};
+
+#[cfg(feature = "engine")]
+use crate::bindings::{CmdSource, ENGINE_FUNCTIONS};
+use crate::commands;
+
+/// runs console commands for the rcon server
+///
+/// the output is expected to show up in the console the server captures
+pub trait CommandExecutor: Send {
+    /// runs the command right away so its output can be collected in the same frame
+    fn execute(&mut self, command: &str);
+
+    /// splits a single command into its argv the way the engine would when running it
+    ///
+    /// `None` if the command can't be tokenized
+    fn tokenize(&mut self, command: &str) -> Option<Vec<String>> {
+        Some(commands::tokenize(command))
+    }
+
+    /// prints a line into the console; used to mark where a command's output starts and ends
+    fn echo(&mut self, text: &str) {
+        self.execute(&format!("echo {text}"));
+    }
+}
+
+/// executes commands through the engine's command buffer
+#[cfg(feature = "engine")]
+pub struct NorthstarExecutor;
+
+#[cfg(feature = "engine")]
+impl CommandExecutor for NorthstarExecutor {
+    fn execute(&mut self, command: &str) {
+        let cmd =
+            try_cstring(command).unwrap_or_else(|_| to_cstring(command.replace('\0', "").as_str()));
+        let funcs = ENGINE_FUNCTIONS.wait();
+        unsafe {
+            (funcs.cbuf_add_text_type)(
+                (funcs.cbuf_get_current_player)(),
+                cmd.as_ptr(),
+                CmdSource::Code,
+            );
+
+            (funcs.cbuf_execute)() // execute the buffer rn since we want the results immediately
+        }
+    }
+
+    fn tokenize(&mut self, command: &str) -> Option<Vec<String>> {
+        let cmd = try_cstring(command).ok()?;
+        let funcs = ENGINE_FUNCTIONS.wait();
+        let mut ccommand = MaybeUninit::<CCommand>::zeroed();
+
+        unsafe {
+            if !(funcs.ccommand_tokenize)(ccommand.as_mut_ptr(), cmd.as_ptr(), CmdSource::Code) {
+                return None;
+            }
+
+            let ccommand = ccommand.assume_init_ref();
+            Some(
+                ccommand.m_ppArgv[..ccommand.m_nArgc as usize]
+                    .iter()
+                    .map(|arg| CStr::from_ptr(*arg).to_string_lossy().to_string())
+                    .collect(),
+            )
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    fmt,
+    ops::Range,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+use crate::args::split_args;
+
+const MAX_LINES: usize = 10_000;
+const MAX_BYTES: usize = 4 * 1024 * 1024;
+/// a dump stops at whichever comes first so a page never gets near the write buffer limit
+const DUMP_PAGE_LINES: usize = 1000;
+const DUMP_PAGE_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+    #[error("{0} needs a value")]
+    MissingValue(String),
+
+    #[error("{option} takes a number, not {value}")]
+    InvalidNumber { option: String, value: String },
+
+    #[error("invalid pattern for {option}: {err}")]
+    InvalidPattern { option: String, err: regex::Error },
+
+    #[error("{0} isn't a dumpconsole option; expected last, since, since_time, include, exclude, limit or page")]
+    UnknownOption(String),
+
+    #[error("there's a quote that's never closed")]
+    UnterminatedQuote,
+}
+
+/// how much console history is kept; the oldest lines go first once either is hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryLimits {
+    pub max_lines: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_lines: MAX_LINES,
+            max_bytes: MAX_BYTES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleLine {
+    /// goes up by one for every line so a client can tell if it missed any
+    pub seq: u64,
+    pub time: SystemTime,
+    pub text: String,
+}
+
+/// the last lines the console printed
+#[derive(Debug, Default)]
+pub struct ConsoleHistory {
+    lines: VecDeque<ConsoleLine>,
+    bytes: usize,
+    next_seq: u64,
+    limits: HistoryLimits,
+}
+
+impl ConsoleHistory {
+    pub fn new(limits: HistoryLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// forgets whatever doesn't fit anymore
+    pub fn set_limits(&mut self, limits: HistoryLimits) {
+        self.limits = limits;
+        self.trim();
+    }
+
+    pub fn limits(&self) -> HistoryLimits {
+        self.limits
+    }
+
+    pub fn push(&mut self, text: String) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        self.bytes += text.len();
+        self.lines.push_back(ConsoleLine {
+            seq,
+            time: SystemTime::now(),
+            text,
+        });
+        self.trim();
+
+        seq
+    }
+
+    /// the newest line is always kept even if it's over the byte budget on its own
+    fn trim(&mut self) {
+        while self.lines.len() > 1
+            && (self.lines.len() > self.limits.max_lines || self.bytes > self.limits.max_bytes)
+        {
+            if let Some(line) = self.lines.pop_front() {
+                self.bytes -= line.text.len();
+            }
+        }
+    }
+
+    /// the seq of the oldest line still kept; asking for anything before it means lines were missed
+    pub fn oldest_seq(&self) -> u64 {
+        self.lines.front().map_or(self.next_seq, |line| line.seq)
+    }
+
+    /// the seq the next line will get
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// every kept line with a seq of at least `seq`
+    pub fn since(&self, seq: u64) -> impl DoubleEndedIterator<Item = &ConsoleLine> {
+        // seqs have no holes so the index can be worked out
+        let skip = seq.saturating_sub(self.oldest_seq()) as usize;
+        self.lines.iter().skip(skip)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &ConsoleLine> {
+        self.lines.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// how many bytes of text are kept
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+/// what `dumpconsole` was asked for
+#[derive(Debug, Clone, Default)]
+pub struct DumpQuery {
+    /// only the newest this many lines; ignored once paging since the page already says where to start
+    pub last: Option<usize>,
+    /// lines from this seq on
+    pub since: Option<u64>,
+    /// lines printed at or after this
+    pub since_time: Option<SystemTime>,
+    pub include: Option<Regex>,
+    pub exclude: Option<Regex>,
+    /// lines per page
+    pub limit: Option<usize>,
+    /// the continuation token from the previous page
+    pub page: Option<u64>,
+}
+
+impl DumpQuery {
+    /// `option value` pairs like `last 2000 exclude "^\[debug\]"`; quote values with spaces in them
+    pub fn parse(args: &str) -> Result<Self, DumpError> {
+        let mut query = Self::default();
+        let mut words = split_args(args)
+            .ok_or(DumpError::UnterminatedQuote)?
+            .into_iter();
+
+        while let Some(option) = words.next() {
+            let value = words
+                .next()
+                .ok_or_else(|| DumpError::MissingValue(option.clone()))?;
+
+            match option.as_str() {
+                "last" => query.last = Some(number(&option, &value)?),
+                "since" => query.since = Some(number(&option, &value)?),
+                "since_time" => {
+                    query.since_time =
+                        Some(UNIX_EPOCH + Duration::from_secs(number(&option, &value)?))
+                }
+                "include" => query.include = Some(pattern(&option, &value)?),
+                "exclude" => query.exclude = Some(pattern(&option, &value)?),
+                "limit" => query.limit = Some(number(&option, &value)?),
+                "page" => query.page = Some(number(&option, &value)?),
+                _ => Err(DumpError::UnknownOption(option))?,
+            }
+        }
+
+        Ok(query)
+    }
+
+    fn wants(&self, line: &ConsoleLine) -> bool {
+        // so `$` matches the end of the line
+        let text = line.text.trim_end_matches(['\r', '\n']);
+
+        self.since_time.is_none_or(|time| line.time >= time)
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|include| include.is_match(text))
+            && self
+                .exclude
+                .as_ref()
+                .is_none_or(|exclude| !exclude.is_match(text))
+    }
+}
+
+fn number<T: FromStr>(option: &str, value: &str) -> Result<T, DumpError> {
+    value.parse().map_err(|_| DumpError::InvalidNumber {
+        option: option.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn pattern(option: &str, value: &str) -> Result<Regex, DumpError> {
+    Regex::new(value).map_err(|err| DumpError::InvalidPattern {
+        option: option.to_string(),
+        err,
+    })
+}
+
+/// one page of a `dumpconsole`
+#[derive(Debug)]
+pub struct Dump<'a> {
+    pub lines: Vec<&'a ConsoleLine>,
+    /// the `page` to ask for next when it didn't all fit
+    pub next_page: Option<u64>,
+    /// the `since` that picks up right after this dump once it's all been read
+    pub next_seq: u64,
+    /// seqs that were asked for but already fell out of the history
+    pub missed: Option<Range<u64>>,
+}
+
+impl ConsoleHistory {
+    /// a page also stops before `max_bytes` so it can't fill up the client's write buffer
+    pub fn dump(&self, query: &DumpQuery, max_bytes: usize) -> Dump<'_> {
+        let max_bytes = max_bytes.min(DUMP_PAGE_BYTES);
+        let start = query.page.or(query.since);
+        let missed = start
+            .filter(|start| *start < self.oldest_seq())
+            .map(|start| start..self.oldest_seq());
+
+        let mut lines = self
+            .since(start.unwrap_or(0))
+            .filter(|line| query.wants(line))
+            .collect::<Vec<_>>();
+
+        // without anything saying where to start it's the newest page
+        let limit = query.limit.unwrap_or(DUMP_PAGE_LINES).max(1);
+        let last = match query {
+            DumpQuery { page: Some(_), .. } => None,
+            DumpQuery {
+                last: Some(last), ..
+            } => Some(*last),
+            DumpQuery {
+                since: None,
+                since_time: None,
+                ..
+            } => Some(limit),
+            _ => None,
+        };
+        if let Some(last) = last {
+            _ = lines.drain(..lines.len().saturating_sub(last));
+        }
+
+        let mut bytes = 0;
+        let end = lines
+            .iter()
+            .enumerate()
+            .position(|(i, line)| {
+                bytes += line.text.len();
+                i >= limit || (i > 0 && bytes > max_bytes)
+            })
+            .unwrap_or(lines.len());
+        let next_page = lines.get(end).map(|line| line.seq);
+        lines.truncate(end);
+
+        Dump {
+            lines,
+            next_page,
+            next_seq: self.next_seq,
+            missed,
+        }
+    }
+}
+
+/// one line per console line with notes about missed lines and where to continue around them
+impl fmt::Display for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(missed) = &self.missed {
+            writeln!(
+                f,
+                "dumpconsole: lines {} to {} aren't kept anymore",
+                missed.start,
+                missed.end - 1
+            )?;
+        }
+
+        for line in &self.lines {
+            writeln!(f, "{}", line.text.trim_end_matches(['\r', '\n']))?;
+        }
+
+        match self.next_page {
+            Some(page) => writeln!(f, "dumpconsole: there's more; continue with page {page}"),
+            None => writeln!(
+                f,
+                "dumpconsole: that's all; continue with since {}",
+                self.next_seq
+            ),
+        }
+    }
+}
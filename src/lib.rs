@@ -1,32 +1,62 @@
+#[cfg(feature = "engine")]
+use args::RconArgs;
+#[cfg(feature = "engine")]
 use bindings::{EngineFunctions, ENGINE_FUNCTIONS};
+#[cfg(feature = "engine")]
+use config::{CaptureConfig, Config};
+#[cfg(feature = "engine")]
 use console_hook::{hook_console_print, hook_write_console};
+#[cfg(feature = "engine")]
+use executor::NorthstarExecutor;
+#[cfg(feature = "engine")]
 use parking_lot::Mutex;
+#[cfg(feature = "engine")]
+use password::PASSWORD_ENV_VAR;
+#[cfg(feature = "engine")]
 use rcon::RconServer;
+#[cfg(feature = "engine")]
 use rrplug::{bindings::plugin_abi::PluginColor, mid::engine::WhichDll, prelude::*};
+#[cfg(feature = "engine")]
 use std::{
     cell::RefCell,
-    collections::HashMap,
     env,
+    ffi::OsString,
+    os::windows::ffi::OsStringExt,
+    path::{Path, PathBuf},
     sync::mpsc::{self, Sender},
 };
+#[cfg(feature = "engine")]
+use windows_sys::Win32::System::LibraryLoader::{
+    GetModuleFileNameW, GetModuleHandleExW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+    GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+};
 
+pub mod accounts;
+pub mod args;
+#[cfg(feature = "engine")]
 pub mod bindings;
+pub mod cidr;
+pub mod commands;
+pub mod config;
 pub mod console;
+#[cfg(feature = "engine")]
 pub mod console_hook;
+pub mod executor;
+pub mod history;
+pub mod packet;
+pub mod password;
+pub mod permissions;
 pub mod rcon;
+pub mod throttle;
 
-const VALID_RCON_ARGS: [&str; 4] = [
-    "rcon_ip_port",
-    "rcon_password",
-    "-rcon_ip_port",
-    "-rcon_password",
-];
-
+#[cfg(feature = "engine")]
 pub struct RconPlugin {
     console_sender: Mutex<Sender<String>>,
     server: Option<EngineGlobal<RefCell<RconServer>>>,
+    capture: CaptureConfig,
 }
 
+#[cfg(feature = "engine")]
 impl Plugin for RconPlugin {
     const PLUGIN_INFO: PluginInfo = PluginInfo::new_with_color(
         c"r2rcon-rs",
@@ -43,33 +73,44 @@ impl Plugin for RconPlugin {
     fn new(_reloaded: bool) -> Self {
         let (console_sender, console_recv) = mpsc::channel();
 
-        let rcon_args = env::args()
-            .zip(env::args().skip(1))
-            .filter(|(cmd, _)| VALID_RCON_ARGS.contains(&&cmd[..]))
-            .fold(HashMap::new(), |mut hash_map, (cmd, arg)| {
-                _ = hash_map.insert(cmd, arg);
-                hash_map
-            });
-
         let mut server = None;
+        let mut capture = CaptureConfig::default();
 
         'start_server: {
-            let (Some(bind_ip), Some(password)) = (
-                rcon_args
-                    .get(VALID_RCON_ARGS[0])
-                    .or_else(|| rcon_args.get(VALID_RCON_ARGS[2])),
-                rcon_args
-                    .get(VALID_RCON_ARGS[1])
-                    .or_else(|| rcon_args.get(VALID_RCON_ARGS[3])),
-            ) else {
-                log::error!("the rcon args that were provided are invalid!");
-                break 'start_server;
+            let args = match RconArgs::from_env() {
+                Ok(args) => args,
+                Err(err) => {
+                    log::error!("rcon isn't starting because of the startup args : {err}");
+                    break 'start_server;
+                }
+            };
+
+            let config = match Config::find(args.config.as_deref(), plugin_dir().as_deref()) {
+                Some(path) => Config::load(&path),
+                None => Ok(Config::default()),
+            };
+
+            let settings = match config
+                .and_then(|config| config.resolve(&args, env::var(PASSWORD_ENV_VAR).ok()))
+            {
+                Ok(settings) => settings,
+                Err(err) => {
+                    log::error!("rcon isn't starting because the config is invalid : {err}");
+                    break 'start_server;
+                }
             };
 
-            server = RconServer::try_new(bind_ip, password, console_recv)
+            log::set_max_level(settings.log_level);
+            log::info!("{settings}");
+
+            capture = settings.capture.clone();
+            server = settings
+                .start_server(console_recv, NorthstarExecutor)
                 .map_err(|err| log::info!("failed to connect to socket : {err:?}"))
                 .inspect(|_| {
-                    hook_write_console();
+                    if capture.write_console {
+                        hook_write_console();
+                    }
                 })
                 .ok();
         }
@@ -77,13 +118,14 @@ impl Plugin for RconPlugin {
         Self {
             console_sender: Mutex::new(console_sender),
             server: server.map(|s| EngineGlobal::new(RefCell::new(s))),
+            capture,
         }
     }
 
     fn on_dll_load(&self, _: Option<&EngineData>, dll_ptr: &DLLPointer, _token: EngineToken) {
         unsafe { EngineFunctions::try_init(dll_ptr, &ENGINE_FUNCTIONS) };
 
-        if let WhichDll::Client = dll_ptr.which_dll() {
+        if let (WhichDll::Client, true) = (dll_ptr.which_dll(), self.capture.print) {
             let addr = dll_ptr.get_dll_ptr() as isize;
             std::thread::spawn(move || _ = hook_console_print(addr));
         }
@@ -97,4 +139,29 @@ impl Plugin for RconPlugin {
     }
 }
 
+/// the folder the plugin's dll was loaded from
+#[cfg(feature = "engine")]
+fn plugin_dir() -> Option<PathBuf> {
+    let mut module = 0;
+    let mut path = [0u16; 1024];
+
+    let len = unsafe {
+        if GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            plugin_dir as *const () as *const u16,
+            &mut module,
+        ) == 0
+        {
+            return None;
+        }
+
+        GetModuleFileNameW(module, path.as_mut_ptr(), path.len() as u32) as usize
+    };
+
+    (len != 0)
+        .then(|| PathBuf::from(OsString::from_wide(&path[..len])))
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+}
+
+#[cfg(feature = "engine")]
 entry!(RconPlugin);
@@ -0,0 +1,190 @@
+use std::borrow::Cow;
+use thiserror::Error;
+
+pub const MAX_PACKET_SIZE: usize = 4096;
+pub const MIN_PACKET_SIZE: usize = 10;
+pub const SIZE_FIELD_LEN: usize = 4;
+pub const MAX_CONTENT_SIZE: usize = MAX_PACKET_SIZE - MIN_PACKET_SIZE;
+
+#[derive(Debug, Error)]
+pub enum PacketError {
+    #[error("the packet size can't be negative but got {0}")]
+    NegativeSize(i32),
+
+    #[error("the packet size has to be at least {MIN_PACKET_SIZE} but got {0}")]
+    TooSmall(usize),
+
+    #[error("the packet size can't be more than {max} but got {size}")]
+    TooLarge { size: usize, max: usize },
+
+    #[error("the packet body wasn't null terminated")]
+    MissingNullTerminator,
+
+    #[error("invalid packet type {0}")]
+    InvalidType(i32),
+}
+
+/// which way a packet is going, needed since `SERVERDATA_EXECCOMMAND` and `SERVERDATA_AUTH_RESPONSE` share a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// client to server
+    Request,
+    /// server to client
+    Response,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketType {
+    /// `SERVERDATA_AUTH`
+    Auth,
+    /// `SERVERDATA_AUTH_RESPONSE`
+    AuthResponse,
+    /// `SERVERDATA_EXECCOMMAND`
+    ExecCommand,
+    /// `SERVERDATA_RESPONSE_VALUE`
+    ResponseValue,
+}
+
+impl PacketType {
+    pub const fn to_i32(self) -> i32 {
+        match self {
+            PacketType::Auth => 3,
+            PacketType::AuthResponse | PacketType::ExecCommand => 2,
+            PacketType::ResponseValue => 0,
+        }
+    }
+
+    pub const fn from_i32(ty: i32, direction: Direction) -> Option<Self> {
+        match (ty, direction) {
+            (3, Direction::Request) => Some(PacketType::Auth),
+            (2, Direction::Request) => Some(PacketType::ExecCommand),
+            (2, Direction::Response) => Some(PacketType::AuthResponse),
+            (0, _) => Some(PacketType::ResponseValue),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconPacket<'a> {
+    pub id: i32,
+    pub ty: PacketType,
+    pub body: Cow<'a, str>,
+}
+
+impl<'a> RconPacket<'a> {
+    pub fn new(id: i32, ty: PacketType, body: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            id,
+            ty,
+            body: body.into(),
+        }
+    }
+
+    /// the value of the size field; doesn't count the size field itself
+    pub fn size(&self) -> usize {
+        MIN_PACKET_SIZE + self.body.len()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(SIZE_FIELD_LEN + self.size());
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(self.size() as i32).to_le_bytes());
+        buf.extend_from_slice(&self.id.to_le_bytes());
+        buf.extend_from_slice(&self.ty.to_i32().to_le_bytes());
+        buf.extend_from_slice(self.body.as_bytes());
+        buf.extend_from_slice(b"\0\0");
+    }
+
+    /// decodes the packet at the start of `buf` returning it with the amount of bytes it took up
+    ///
+    /// returns `None` if `buf` doesn't hold the whole packet yet.
+    /// the size field is validated before anything else so garbage sizes fail right away.
+    /// the body borrows from `buf` unless it isn't valid utf-8.
+    pub fn decode(
+        buf: &'a [u8],
+        direction: Direction,
+        max_packet_size: usize,
+    ) -> Result<Option<(Self, usize)>, PacketError> {
+        let Some(size_buf) = buf.first_chunk::<SIZE_FIELD_LEN>() else {
+            return Ok(None);
+        };
+
+        let size = i32::from_le_bytes(*size_buf);
+        let size: usize = size.try_into().or(Err(PacketError::NegativeSize(size)))?;
+
+        if size < MIN_PACKET_SIZE {
+            Err(PacketError::TooSmall(size))?
+        }
+        if size > max_packet_size {
+            Err(PacketError::TooLarge {
+                size,
+                max: max_packet_size,
+            })?
+        }
+
+        let Some(packet) = buf.get(SIZE_FIELD_LEN..SIZE_FIELD_LEN + size) else {
+            return Ok(None);
+        };
+
+        let Some(packet) = packet.strip_suffix(b"\0\0") else {
+            Err(PacketError::MissingNullTerminator)?
+        };
+
+        // the size was checked to fit both fields above
+        let (id, rest) = packet
+            .split_first_chunk::<4>()
+            .ok_or(PacketError::TooSmall(size))?;
+        let (ty, body) = rest
+            .split_first_chunk::<4>()
+            .ok_or(PacketError::TooSmall(size))?;
+
+        let id = i32::from_le_bytes(*id);
+        let ty = i32::from_le_bytes(*ty);
+        let ty = PacketType::from_i32(ty, direction).ok_or(PacketError::InvalidType(ty))?;
+
+        Ok(Some((
+            Self {
+                id,
+                ty,
+                body: String::from_utf8_lossy(body),
+            },
+            SIZE_FIELD_LEN + size,
+        )))
+    }
+
+    pub fn into_owned(self) -> RconPacket<'static> {
+        RconPacket {
+            id: self.id,
+            ty: self.ty,
+            body: Cow::Owned(self.body.into_owned()),
+        }
+    }
+}
+
+/// decodes every complete packet in `buf` and removes them from it
+///
+/// incomplete packets are left in the buffer until the rest of their bytes arrive
+pub fn decode_packets(
+    buf: &mut Vec<u8>,
+    direction: Direction,
+    max_packet_size: usize,
+) -> Result<Vec<RconPacket<'static>>, PacketError> {
+    let mut packets = Vec::new();
+    let mut consumed = 0;
+
+    while let Some((packet, packet_len)) =
+        RconPacket::decode(&buf[consumed..], direction, max_packet_size)?
+    {
+        packets.push(packet.into_owned());
+        consumed += packet_len;
+    }
+
+    buf.drain(..consumed);
+
+    Ok(packets)
+}
@@ -0,0 +1,207 @@
+use argon2::{
+    password_hash::{self, PasswordHash, PasswordHashString},
+    Argon2, Params,
+};
+use pbkdf2::{Params as Pbkdf2Params, Pbkdf2};
+use serde::{de, Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+pub const PASSWORD_ENV_VAR: &str = "R2RCON_PASSWORD";
+const HASH_PREFIXES: [&str; 2] = ["$argon2", "$pbkdf2"];
+const SUPPORTED_ALGORITHMS: [&str; 5] = [
+    "argon2id",
+    "argon2i",
+    "argon2d",
+    "pbkdf2-sha256",
+    "pbkdf2-sha512",
+];
+/// hashes get checked on the game's frame so anything slower than roughly 50ms is refused
+const MAX_ARGON2_COST: u32 = 48 * 1024;
+const MAX_PBKDF2_ROUNDS: u32 = 100_000;
+
+#[derive(Debug, Error)]
+pub enum PasswordError {
+    #[error("the password hash is malformed: {0}")]
+    InvalidHash(password_hash::Error),
+
+    #[error("the password hash is missing its salt or hash")]
+    Incomplete,
+
+    #[error("{0} hashes aren't supported; use argon2 or pbkdf2")]
+    UnsupportedAlgorithm(String),
+
+    #[error("the password hash is too slow to check during a frame; {0}")]
+    TooExpensive(String),
+
+    #[error("couldn't read the password file {path}: {err}")]
+    UnreadableFile { path: PathBuf, err: io::Error },
+
+    #[error("the password file {0} is empty")]
+    EmptyFile(PathBuf),
+}
+
+/// a password in plain text or as an argon2 or pbkdf2 hash in the PHC string format
+///
+/// parsing treats anything starting with `$argon2` or `$pbkdf2` as a hash so one can go anywhere a password can
+#[derive(Clone)]
+pub enum Password {
+    Plain(String),
+    Hash(PasswordHashString),
+}
+
+impl Password {
+    pub fn hash(phc: &str) -> Result<Self, PasswordError> {
+        let hash = PasswordHash::new(phc).map_err(PasswordError::InvalidHash)?;
+
+        if !SUPPORTED_ALGORITHMS.contains(&hash.algorithm.as_str()) {
+            Err(PasswordError::UnsupportedAlgorithm(
+                hash.algorithm.to_string(),
+            ))?
+        }
+
+        if hash.salt.is_none() || hash.hash.is_none() {
+            Err(PasswordError::Incomplete)?
+        }
+
+        let param = |name: &str, default: u32| hash.params.get_decimal(name).unwrap_or(default);
+        if hash.algorithm.as_str().starts_with("argon2") {
+            let cost = param("m", Params::DEFAULT_M_COST) as u64
+                * param("t", Params::DEFAULT_T_COST) as u64;
+            if cost > MAX_ARGON2_COST as u64 {
+                Err(PasswordError::TooExpensive(format!(
+                    "argon2 memory (m) times passes (t) can be at most {MAX_ARGON2_COST} but it's {cost}"
+                )))?
+            }
+        } else if param("i", Pbkdf2Params::RECOMMENDED_ROUNDS as u32) > MAX_PBKDF2_ROUNDS {
+            Err(PasswordError::TooExpensive(format!(
+                "pbkdf2 can do at most {MAX_PBKDF2_ROUNDS} rounds"
+            )))?
+        }
+
+        Ok(Password::Hash(hash.serialize()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Password::Plain(password) if password.is_empty())
+    }
+
+    /// checks an attempt without leaking how much of it was right through timing
+    ///
+    /// hashes are slow on purpose so this can take a few milliseconds for them
+    pub fn verify(&self, attempt: &str) -> bool {
+        match self {
+            // hashing first so the comparison doesn't depend on the lengths either
+            Password::Plain(password) => Sha256::digest(password)
+                .ct_eq(&Sha256::digest(attempt))
+                .into(),
+            Password::Hash(hash) => hash
+                .password_hash()
+                .verify_password(&[&Argon2::default(), &Pbkdf2], attempt)
+                .is_ok(),
+        }
+    }
+}
+
+impl FromStr for Password {
+    type Err = PasswordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if HASH_PREFIXES.iter().any(|prefix| s.starts_with(prefix)) {
+            Password::hash(s)
+        } else {
+            Ok(Password::Plain(s.to_string()))
+        }
+    }
+}
+
+/// goes through [`FromStr`] unlike the `From` impls so hashes work in config files
+impl<'de> Deserialize<'de> for Password {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl From<&str> for Password {
+    fn from(password: &str) -> Self {
+        Password::Plain(password.to_string())
+    }
+}
+
+impl From<String> for Password {
+    fn from(password: String) -> Self {
+        Password::Plain(password)
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Password::Plain(_) => write!(f, "Plain(..)"),
+            Password::Hash(hash) => write!(f, "Hash({})", hash.algorithm()),
+        }
+    }
+}
+
+/// where a password came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordSource {
+    File(PathBuf),
+    Env,
+    Arg,
+    /// written out in the config file
+    Config,
+}
+
+impl fmt::Display for PasswordSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordSource::File(path) => write!(f, "the file {}", path.display()),
+            PasswordSource::Env => write!(f, "the {PASSWORD_ENV_VAR} environment variable"),
+            PasswordSource::Arg => write!(f, "the command line"),
+            PasswordSource::Config => write!(f, "the config file"),
+        }
+    }
+}
+
+/// picks the password from a file, then the environment variable and then the command line arg
+///
+/// a file that can't be read is an error instead of falling through so a broken mount doesn't start rcon with an old password.
+/// trailing newlines in the file and an empty environment variable are ignored
+pub fn resolve_password(
+    file: Option<&Path>,
+    env: Option<String>,
+    arg: Option<&str>,
+) -> Result<Option<(Password, PasswordSource)>, PasswordError> {
+    if let Some(path) = file {
+        let contents = fs::read_to_string(path).map_err(|err| PasswordError::UnreadableFile {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        let password = contents.trim_end_matches(['\r', '\n']);
+
+        if password.is_empty() {
+            Err(PasswordError::EmptyFile(path.to_path_buf()))?
+        }
+
+        return Ok(Some((
+            password.parse()?,
+            PasswordSource::File(path.to_path_buf()),
+        )));
+    }
+
+    if let Some(password) = env.filter(|password| !password.is_empty()) {
+        return Ok(Some((password.parse()?, PasswordSource::Env)));
+    }
+
+    arg.map(|password| Ok((password.parse()?, PasswordSource::Arg)))
+        .transpose()
+}
@@ -0,0 +1,190 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, str::FromStr};
+use thiserror::Error;
+
+use crate::accounts::Role;
+
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("a command rule can't be empty")]
+    Empty,
+
+    #[error("invalid argument pattern for {command}: {err}")]
+    InvalidPattern { command: String, err: regex::Error },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("{command} isn't allowed for the {role} role")]
+pub struct PermissionDenied {
+    pub command: String,
+    pub role: Role,
+}
+
+/// matches a command by name and optionally its arguments
+///
+/// written as `name` or `name <regex>` where the regex has to match all of the arguments.
+/// `*` as the name matches every command. both ignore case like the engine does so `kick CAT` can't
+/// get around a `kick cat` rule
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CommandRule {
+    command: String,
+    args: Option<(String, Regex)>,
+}
+
+impl CommandRule {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into().to_ascii_lowercase(),
+            args: None,
+        }
+    }
+
+    pub fn with_args(command: impl Into<String>, pattern: &str) -> Result<Self, RuleError> {
+        let command = command.into().to_ascii_lowercase();
+        let args = Regex::new(&format!("(?i)^(?:{pattern})$")).map_err(|err| {
+            RuleError::InvalidPattern {
+                command: command.clone(),
+                err,
+            }
+        })?;
+
+        Ok(Self {
+            command,
+            args: Some((pattern.to_string(), args)),
+        })
+    }
+
+    pub fn matches(&self, command: &str, args: &str) -> bool {
+        (self.command == "*" || self.command.eq_ignore_ascii_case(command))
+            && self
+                .args
+                .as_ref()
+                .is_none_or(|(_, pattern)| pattern.is_match(args))
+    }
+}
+
+impl FromStr for CommandRule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(char::is_whitespace) {
+            Some((command, pattern)) => CommandRule::with_args(command, pattern.trim()),
+            None if s.trim().is_empty() => Err(RuleError::Empty),
+            None => Ok(CommandRule::new(s.trim())),
+        }
+    }
+}
+
+impl TryFrom<String> for CommandRule {
+    type Error = RuleError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for CommandRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.args {
+            Some((pattern, _)) => write!(f, "{} {pattern}", self.command),
+            None => write!(f, "{}", self.command),
+        }
+    }
+}
+
+/// what a role can run; deny rules win over allow rules and nothing runs unless a rule allows it
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoleRules {
+    pub allow: Vec<CommandRule>,
+    pub deny: Vec<CommandRule>,
+}
+
+impl RoleRules {
+    pub fn allow_all() -> Self {
+        Self {
+            allow: vec![CommandRule::new("*")],
+            deny: Vec::new(),
+        }
+    }
+
+    pub fn allows(&self, command: &str, args: &str) -> bool {
+        !self.deny.iter().any(|rule| rule.matches(command, args))
+            && self.allow.iter().any(|rule| rule.matches(command, args))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Permissions {
+    rules: HashMap<Role, RoleRules>,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            rules: HashMap::from([
+                (Role::Owner, RoleRules::allow_all()),
+                (
+                    Role::Moderator,
+                    RoleRules {
+                        allow: ["kick", "say", "status"]
+                            .into_iter()
+                            .map(CommandRule::new)
+                            .collect(),
+                        deny: Vec::new(),
+                    },
+                ),
+                (
+                    Role::Viewer,
+                    RoleRules {
+                        allow: ["dumpconsole", "rcon_subscribe"]
+                            .into_iter()
+                            .map(CommandRule::new)
+                            .collect(),
+                        deny: Vec::new(),
+                    },
+                ),
+            ]),
+        }
+    }
+}
+
+impl Permissions {
+    pub fn set_rules(&mut self, role: Role, rules: RoleRules) {
+        _ = self.rules.insert(role, rules);
+    }
+
+    pub fn rules(&self, role: Role) -> Option<&RoleRules> {
+        self.rules.get(&role)
+    }
+
+    /// whether the role may see whatever the console prints, like through `dumpconsole`
+    pub fn can_read_console(&self, role: Role) -> bool {
+        self.rules
+            .get(&role)
+            .is_some_and(|rules| rules.allows("dumpconsole", ""))
+    }
+
+    /// checks a tokenized command; the arguments are matched joined by single spaces
+    pub fn check(&self, role: Role, argv: &[String]) -> Result<(), PermissionDenied> {
+        let (command, args) = argv
+            .split_first()
+            .map(|(command, args)| (command.as_str(), args.join(" ")))
+            .unwrap_or_default();
+
+        if self
+            .rules
+            .get(&role)
+            .is_some_and(|rules| rules.allows(command, &args))
+        {
+            Ok(())
+        } else {
+            Err(PermissionDenied {
+                command: argv.join(" "),
+                role,
+            })
+        }
+    }
+}
@@ -1,284 +1,886 @@
-use rrplug::mid::utils::{to_cstring, try_cstring};
 use std::{
     io::{self, Read, Write},
-    net::{TcpListener, TcpStream},
-    sync::mpsc::Receiver,
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
 use crate::{
-    bindings::{CmdSource, ENGINE_FUNCTIONS},
-    console::ConsoleAccess,
+    accounts::{Accounts, Identity},
+    cidr::IpFilter,
+    commands::{format_argv, split_commands, tokenize},
+    console::{ConsoleAccess, ConsoleFilter, ConsoleSource},
+    executor::CommandExecutor,
+    history::{DumpQuery, HistoryLimits},
+    packet::{
+        Direction, PacketError, PacketType, RconPacket, MAX_CONTENT_SIZE, MAX_PACKET_SIZE,
+        SIZE_FIELD_LEN,
+    },
+    permissions::Permissions,
+    throttle::{AuthFailure, AuthPolicy, AuthThrottle},
 };
 
-const SERVERDATA_AUTH: i32 = 3;
-const SERVERDATA_EXECCOMMAND: i32 = 2;
-const SERVERDATA_AUTH_RESPONSE: i32 = 2;
-const SERVERDATA_RESPONSE_VALUE: i32 = 0;
-const MAX_PACKET_SIZE: usize = 4096;
-const MIN_PACKET_SIZE: usize = 10;
-pub const MAX_CONTENT_SIZE: usize = MAX_PACKET_SIZE - MIN_PACKET_SIZE;
+const READ_CHUNK_SIZE: usize = 1024;
+const MAX_WRITE_BUFFER: usize = 1024 * 1024;
+/// the smallest write buffer that still fits a full packet
+pub const MIN_WRITE_BUFFER: usize = SIZE_FIELD_LEN + MAX_PACKET_SIZE;
+const MAX_CONNECTIONS: usize = 32;
+const MAX_CONNECTIONS_PER_IP: usize = 4;
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const RESPONSE_FRAMES: u32 = 60;
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+const RESPONSE_QUIET_FRAMES: u32 = 5;
+/// how long a closing connection gets to take the rest of its replies
+const CLOSE_GRACE: Duration = Duration::from_secs(1);
+/// srcds answers a mirrored empty RESPONSE_VALUE with this body right after echoing it back
+const MIRROR_TRAILER_CONTENT: &str = "\0\0\0\u{1}\0\0\0\0";
 
 #[derive(Debug, Error)]
 pub enum RconRequestError {
-    #[error("invalid vec length; converstion failed")]
-    VecToArrayError,
+    #[error(transparent)]
+    Packet(#[from] PacketError),
 
     #[error("invalid request type {0}")]
     InvalidRequestType(i32),
 
-    #[error("a cast failed")]
-    IntCastFail,
+    #[error("sent request {0} without authenticating first")]
+    Unauthenticated(i32),
+
+    #[error("the client closed the connection")]
+    ConnectionClosed,
+
+    #[error("failed to authenticate {0} times")]
+    TooManyAuthFailures(u32),
 
-    #[error("read the wrong amount of bytes out of a stream {MAX_CONTENT_SIZE} got {0}")]
-    ReadWrongAmount(usize),
+    #[error("banned for failing to authenticate too often")]
+    Banned,
 
-    #[error("the connect client provided a invalid id to run a command which was {0}")]
-    InvalidClientID(i32),
+    #[error("didn't authenticate within {0:?}")]
+    AuthTimeout(Duration),
+
+    #[error("idle for longer than {0:?}")]
+    IdleTimeout(Duration),
+
+    #[error("the client is too slow to keep up; {queued} bytes are waiting to be sent but the max is {max}")]
+    WriteBufferFull { queued: usize, max: usize },
 
     #[error(transparent)]
     SocketError(#[from] std::io::Error),
 }
 
-pub struct RconResponse {
+#[derive(Debug, Clone)]
+pub struct RconLimits {
+    /// the biggest size field a client may send before it gets disconnected
+    pub max_packet_size: usize,
+    /// how many bytes can wait to be sent to a client before it gets disconnected for being too slow
+    pub max_write_buffer: usize,
+    /// connections past this get closed right after they're accepted
+    pub max_connections: usize,
+    pub max_connections_per_ip: usize,
+    /// how long a connection has to send a correct AUTH before it gets dropped
+    pub auth_timeout: Duration,
+    /// how long an authenticated session can go without sending anything before it gets dropped
+    ///
+    /// sessions subscribed to the console are only listening so they're left alone
+    pub idle_timeout: Duration,
+    /// how many more frames a command's output gets to show up after the one it ran in
+    pub response_frames: u32,
+    /// how long a command's output gets to show up; whichever runs out first ends the wait
+    pub response_timeout: Duration,
+    /// how many frames in a row nothing new has to come in after a command ran for it to be answered
+    pub response_quiet_frames: u32,
+}
+
+impl Default for RconLimits {
+    fn default() -> Self {
+        Self {
+            max_packet_size: MAX_PACKET_SIZE,
+            max_write_buffer: MAX_WRITE_BUFFER,
+            max_connections: MAX_CONNECTIONS,
+            max_connections_per_ip: MAX_CONNECTIONS_PER_IP,
+            auth_timeout: AUTH_TIMEOUT,
+            idle_timeout: IDLE_TIMEOUT,
+            response_frames: RESPONSE_FRAMES,
+            response_timeout: RESPONSE_TIMEOUT,
+            response_quiet_frames: RESPONSE_QUIET_FRAMES,
+        }
+    }
+}
+
+pub type SessionId = u64;
+
+#[derive(Debug)]
+pub enum DisconnectReason {
+    /// the client shut down its side cleanly
+    Closed,
+    /// the connection was reset or aborted by the peer
+    Reset,
+    Error(RconRequestError),
+}
+
+impl From<RconRequestError> for DisconnectReason {
+    fn from(err: RconRequestError) -> Self {
+        match err {
+            RconRequestError::ConnectionClosed => DisconnectReason::Closed,
+            RconRequestError::SocketError(ref io_err)
+                if matches!(
+                    io_err.kind(),
+                    io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::BrokenPipe
+                ) =>
+            {
+                DisconnectReason::Reset
+            }
+            err => DisconnectReason::Error(err),
+        }
+    }
+}
+
+impl std::fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectReason::Closed => write!(f, "closed by the client"),
+            DisconnectReason::Reset => write!(f, "reset by the client"),
+            DisconnectReason::Error(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// a session that asked for console lines as they come in
+struct Subscription {
+    /// the id of the `rcon_subscribe` request; every streamed line is sent with it
     id: i32,
-    ty: i32,
-    content: String,
+    filter: ConsoleFilter,
 }
 
-#[allow(clippy::from_over_into)]
-impl Into<Vec<u8>> for RconResponse {
-    fn into(self) -> Vec<u8> {
-        let mut buf = Vec::new();
+/// an exec whose output might still be on its way
+///
+/// nothing else from the session is answered until it's done so responses keep their order
+struct PendingRequest {
+    client_id: i32,
+    /// the formatted argv and capture tag of every command in it
+    commands: Vec<(String, String)>,
+    started_at: Instant,
+    frames: u32,
+    /// frames in a row nothing new came in
+    quiet_frames: u32,
+    /// how much output there was last frame
+    seen_bytes: usize,
+}
 
-        self.id.to_le_bytes().into_iter().for_each(|b| buf.push(b));
-        self.ty.to_le_bytes().into_iter().for_each(|b| buf.push(b));
-        self.content
-            .into_bytes()
-            .into_iter()
-            .for_each(|b| buf.push(b));
-        buf.push(b'\0');
-        buf.push(b'\0');
+impl PendingRequest {
+    fn new(client_id: i32, now: Instant) -> Self {
+        Self {
+            client_id,
+            commands: Vec::new(),
+            started_at: now,
+            frames: 0,
+            quiet_frames: 0,
+            seen_bytes: 0,
+        }
+    }
 
-        // size
-        (buf.len() as i32)
-            .to_le_bytes()
-            .into_iter()
-            .rev()
-            .for_each(|b| buf.insert(0, b));
+    /// true once every command ran and the console went quiet, or it waited long enough
+    fn is_ready(&mut self, console: &ConsoleAccess, limits: &RconLimits, now: Instant) -> bool {
+        let (ended, bytes) = self
+            .commands
+            .iter()
+            .map(|(_, tag)| console.capture_progress(tag))
+            .fold((true, 0), |(all_ended, total), (ended, bytes)| {
+                (all_ended && ended, total + bytes)
+            });
+
+        if bytes != self.seen_bytes {
+            self.seen_bytes = bytes;
+            self.quiet_frames = 0;
+        }
+
+        // the end sentinel only means the command ran; the output can keep coming after it
+        if ended && self.quiet_frames >= limits.response_quiet_frames {
+            return true;
+        }
+
+        if self.frames >= limits.response_frames
+            || now.duration_since(self.started_at) >= limits.response_timeout
+        {
+            if !ended {
+                log::debug!(
+                    "request {} gave up waiting for output after {} frames",
+                    self.client_id,
+                    self.frames
+                );
+            }
+            return true;
+        }
 
-        buf
+        self.frames += 1;
+        self.quiet_frames += 1;
+        false
+    }
+
+    fn finish(self, console: &mut ConsoleAccess, now: Instant) -> String {
+        self.commands
+            .into_iter()
+            .map(|(argv, tag)| format!("] {argv}\n{}", console.finish_capture(&tag, now)))
+            .collect()
     }
 }
 
 pub struct RconStream {
+    /// stays the same for the whole session and is never reused
+    pub id: SessionId,
+    pub addr: SocketAddr,
     pub stream: TcpStream,
-    pub auth: bool,
+    /// who the session logged in as; `None` until it authenticates
+    pub identity: Option<Identity>,
+    pub connected_at: Instant,
+    /// when the last packet came in
+    pub last_activity: Instant,
+    failed_auths: u32,
+    subscription: Option<Subscription>,
+    pending: Option<PendingRequest>,
+    /// set when the connection should be dropped once the current responses went out
+    closing: Option<RconRequestError>,
+    /// when `closing` was first seen, so a client that doesn't read can't keep it around
+    closing_since: Option<Instant>,
+    /// the client shut down its side; what it sent before still gets answered
+    eof: bool,
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+}
+
+impl RconStream {
+    pub fn new(id: SessionId, addr: SocketAddr, stream: TcpStream, now: Instant) -> Self {
+        Self {
+            id,
+            addr,
+            stream,
+            identity: None,
+            connected_at: now,
+            last_activity: now,
+            failed_auths: 0,
+            subscription: None,
+            pending: None,
+            closing: None,
+            closing_since: None,
+            eof: false,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+        }
+    }
+
+    /// queues a packet to be sent over the next frames
+    pub fn queue(
+        &mut self,
+        packet: &RconPacket<'_>,
+        max_write_buffer: usize,
+    ) -> Result<(), RconRequestError> {
+        packet.encode_into(&mut self.write_buf);
+
+        if self.write_buf.len() > max_write_buffer {
+            Err(RconRequestError::WriteBufferFull {
+                queued: self.write_buf.len(),
+                max: max_write_buffer,
+            })?
+        }
+
+        Ok(())
+    }
+
+    /// writes as much of the queued output as the socket takes without blocking
+    pub fn flush(&mut self) -> Result<(), RconRequestError> {
+        let mut written = 0;
+
+        while written < self.write_buf.len() {
+            match self.stream.write(&self.write_buf[written..]) {
+                Ok(0) => Err(io::Error::from(io::ErrorKind::WriteZero))?,
+                Ok(bytes_written) => written += bytes_written,
+                Err(err) => match err.kind() {
+                    io::ErrorKind::WouldBlock => break,
+                    io::ErrorKind::Interrupted => continue,
+                    _ => Err(err)?,
+                },
+            }
+        }
+
+        self.write_buf.drain(..written);
+
+        Ok(())
+    }
 }
 
 pub struct RconServer {
-    password: String,
-    server: TcpListener,
+    listeners: Vec<TcpListener>,
     connections: Vec<RconStream>,
+    next_session_id: SessionId,
+    state: ServerState,
+}
+
+/// everything handling a connection needs from the server
+struct ServerState {
+    accounts: Accounts,
+    permissions: Permissions,
+    ip_filter: IpFilter,
+    throttle: AuthThrottle,
     console: ConsoleAccess,
+    executor: Box<dyn CommandExecutor>,
+    limits: RconLimits,
 }
 
 impl RconServer {
     pub fn try_new(
         bind_ip: &str,
-        password: impl Into<String>,
-        console_recv: Receiver<String>,
+        accounts: Accounts,
+        console: impl ConsoleSource + 'static,
+        executor: impl CommandExecutor + 'static,
     ) -> Result<Self, std::io::Error> {
-        let server = TcpListener::bind(bind_ip)?;
-
-        server.set_nonblocking(true)?;
-
-        let rcon_server = Self {
-            password: password.into(),
-            server,
+        let mut rcon_server = Self {
+            listeners: Vec::new(),
             connections: Vec::new(),
-            console: ConsoleAccess::new(console_recv),
+            next_session_id: 0,
+            state: ServerState {
+                accounts,
+                permissions: Permissions::default(),
+                ip_filter: IpFilter::default(),
+                throttle: AuthThrottle::default(),
+                console: ConsoleAccess::new(console),
+                executor: Box::new(executor),
+                limits: RconLimits::default(),
+            },
         };
 
+        rcon_server.listen(bind_ip)?;
+
         Ok(rcon_server)
     }
 
+    /// accepts connections on another address too
+    pub fn listen(&mut self, bind_ip: &str) -> Result<(), std::io::Error> {
+        let listener = TcpListener::bind(bind_ip)?;
+
+        listener.set_nonblocking(true)?;
+        self.listeners.push(listener);
+
+        Ok(())
+    }
+
+    /// the address of the first listener
+    pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
+        self.listeners[0].local_addr()
+    }
+
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.state.permissions = permissions;
+    }
+
+    pub fn set_ip_filter(&mut self, ip_filter: IpFilter) {
+        self.state.ip_filter = ip_filter;
+    }
+
+    /// also forgets every failure and ban so far
+    pub fn set_auth_policy(&mut self, policy: AuthPolicy) {
+        self.state.throttle = AuthThrottle::new(policy);
+    }
+
+    pub fn set_history_limits(&mut self, limits: HistoryLimits) {
+        self.state.console.set_history_limits(limits);
+    }
+
+    /// limits too small for a full packet are raised to fit one since every client would be dropped otherwise
+    pub fn set_limits(&mut self, mut limits: RconLimits) {
+        limits.max_packet_size = limits.max_packet_size.max(MAX_PACKET_SIZE);
+        limits.max_write_buffer = limits.max_write_buffer.max(MIN_WRITE_BUFFER);
+        self.state.limits = limits;
+    }
+
     pub fn run(&mut self) {
-        while self.console.next_line_catpure().is_some() {} // string allocation could be remove
-
-        match self.server.accept() {
-            Ok((conn, addr)) => match conn.set_nonblocking(true) {
-                Ok(_) => {
-                    log::info!("connection created with {addr:?}");
-                    self.connections.push(RconStream {
-                        stream: conn,
-                        auth: false,
-                    })
+        self.state.console.drain();
+        let fresh_lines = self.state.console.take_fresh_lines();
+
+        let now = Instant::now();
+        self.state.throttle.prune(now);
+        // a capture that was given up on only waits for its end sentinel as long as a request would
+        self.state
+            .console
+            .prune_captures(now, self.state.limits.response_timeout);
+
+        self.accept_connections(now);
+
+        let state = &mut self.state;
+        self.connections.retain_mut(|conn| {
+            let Err(err) = stream_console(conn, &fresh_lines, state.limits.max_write_buffer)
+                .and_then(|_| handle_connection(conn, state, now))
+            else {
+                return true;
+            };
+
+            if let Some(pending) = conn.pending.take() {
+                _ = pending.finish(&mut state.console, now);
+            }
+
+            match DisconnectReason::from(err) {
+                reason @ DisconnectReason::Error(_) => {
+                    log::warn!("session {} ({}) dropped: {reason}", conn.id, conn.addr)
                 }
-                Err(err) => log::error!("failed to connect to a stream: {err}"),
-            },
-            Err(err) => match err.kind() {
-                io::ErrorKind::WouldBlock => {}
-                _ => log::warn!("connection failed because of {err}"),
-            },
-        }
+                reason => log::info!("session {} ({}) {reason}", conn.id, conn.addr),
+            }
 
-        for i in 0..self.connections.len() {
-            match handle_connection(&mut self.connections[i], &self.password, &mut self.console) {
-                Ok(_) => {}
-                Err(err) => {
-                    match &err {
-                        RconRequestError::SocketError(err) => match err.kind() {
-                            io::ErrorKind::WouldBlock => continue,
-                            _ => log::error!("{err}"),
-                        },
-                        _ => log::error!("{err}"),
-                    }
+            false
+        });
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
 
-                    log::info!("terminating a connection");
-                    self.connections.remove(i); // this doesn't seam to prevent future connections from the same connection O_o
-                    break;
+    fn accept_connections(&mut self, now: Instant) {
+        for listener in &self.listeners {
+            loop {
+                match listener.accept() {
+                    // dropping the stream closes it before anything gets read from it
+                    Ok((_, addr)) if !self.state.ip_filter.permits(addr.ip()) => {
+                        log::warn!(
+                            "refused a connection from {addr} since the ip filter doesn't allow it"
+                        );
+                    }
+                    Ok((_, addr)) if self.state.throttle.is_banned(addr.ip(), now) => {
+                        log::warn!("refused a connection from {addr} since it's banned");
+                    }
+                    Ok((_, addr))
+                        if self.connections.len() >= self.state.limits.max_connections =>
+                    {
+                        log::warn!(
+                        "refused a connection from {addr} since there are already {} connections",
+                        self.connections.len()
+                    );
+                    }
+                    Ok((_, addr))
+                        if self
+                            .connections
+                            .iter()
+                            .filter(|conn| conn.addr.ip() == addr.ip())
+                            .count()
+                            >= self.state.limits.max_connections_per_ip =>
+                    {
+                        log::warn!(
+                            "refused a connection from {addr} since {} already has {} connections",
+                            addr.ip(),
+                            self.state.limits.max_connections_per_ip
+                        );
+                    }
+                    Ok((conn, addr)) => match conn.set_nonblocking(true) {
+                        Ok(_) => {
+                            let id = self.next_session_id;
+                            self.next_session_id += 1;
+
+                            log::info!("session {id} connected from {addr}");
+                            self.connections.push(RconStream::new(id, addr, conn, now))
+                        }
+                        Err(err) => log::error!("failed to connect to a stream from {addr}: {err}"),
+                    },
+                    Err(err) => match err.kind() {
+                        io::ErrorKind::WouldBlock => break,
+                        _ => {
+                            log::warn!("connection failed because of {err}");
+                            break;
+                        }
+                    },
                 }
             }
         }
     }
 }
 
-pub fn handle_connection(
+/// queues the new console lines a subscribed session wants
+fn stream_console(
     conn: &mut RconStream,
-    password: &str,
-    console: &mut ConsoleAccess,
+    lines: &[String],
+    max_write_buffer: usize,
 ) -> Result<(), RconRequestError> {
-    let (client_id, request_type, content) = read_rcon_stream(&mut conn.stream)?;
+    let Some(subscription) = &conn.subscription else {
+        return Ok(());
+    };
 
-    let response = parse_response(conn, password, console, client_id, request_type, content)?;
+    let packets = lines
+        .iter()
+        .filter(|line| subscription.filter.matches(line))
+        .flat_map(|line| split_response(subscription.id, line))
+        .collect::<Vec<RconPacket<'static>>>();
 
-    let buf: Vec<u8> = response.into();
-    conn.stream.write_all(&buf)?;
+    for packet in packets {
+        conn.queue(&packet, max_write_buffer)?;
+    }
 
     Ok(())
 }
 
-fn read_rcon_stream(stream: &mut TcpStream) -> Result<(i32, i32, String), RconRequestError> {
-    let mut size_buf = vec![0; 4];
-    let bytes_read = stream.read(&mut size_buf)?;
-    if bytes_read != 4 {
-        Err(RconRequestError::ReadWrongAmount(bytes_read))?
+fn handle_connection(
+    conn: &mut RconStream,
+    state: &mut ServerState,
+    now: Instant,
+) -> Result<(), RconRequestError> {
+    let max_packet_size = state.limits.max_packet_size;
+
+    fill_read_buffer(conn, max_packet_size)?;
+
+    // bans are checked at accept but connections from that ip that were already open go too
+    if conn.identity.is_none()
+        && conn.closing.is_none()
+        && state.throttle.is_banned(conn.addr.ip(), now)
+    {
+        conn.closing = Some(RconRequestError::Banned);
     }
 
-    let size = i32::from_le_bytes(
-        size_buf
-            .into_iter()
-            .collect::<Vec<u8>>()
-            .try_into()
-            .or(Err(RconRequestError::VecToArrayError))?,
-    )
-    .try_into()
-    .or(Err(RconRequestError::IntCastFail))?;
-
-    let mut buf = vec![0; size];
-
-    let bytes_read = stream.read(&mut buf)?;
-    if bytes_read != size && size <= MAX_PACKET_SIZE {
-        Err(RconRequestError::ReadWrongAmount(bytes_read))?
+    loop {
+        if let Some(pending) = &mut conn.pending {
+            if !pending.is_ready(&state.console, &state.limits, now) {
+                break;
+            }
+
+            let pending = conn.pending.take().expect("checked above");
+            let client_id = pending.client_id;
+            for response in split_response(client_id, &pending.finish(&mut state.console, now)) {
+                conn.queue(&response, state.limits.max_write_buffer)?;
+            }
+        }
+
+        // requests from an ip that just failed to authenticate wait in the buffer until its delay is over
+        if conn.closing.is_some()
+            || (conn.identity.is_none() && state.throttle.is_delayed(conn.addr.ip(), now))
+        {
+            break;
+        }
+
+        let Some((request, packet_len)) =
+            RconPacket::decode(&conn.read_buf, Direction::Request, max_packet_size)?
+        else {
+            break;
+        };
+        let request = request.into_owned();
+        conn.read_buf.drain(..packet_len);
+        conn.last_activity = now;
+
+        for response in parse_response(conn, state, request, now)? {
+            conn.queue(&response, state.limits.max_write_buffer)?;
+        }
+    }
+
+    if conn.eof
+        && conn.closing.is_none()
+        && conn.pending.is_none()
+        && RconPacket::decode(&conn.read_buf, Direction::Request, max_packet_size)?.is_none()
+    {
+        conn.closing = Some(RconRequestError::ConnectionClosed);
+    }
+
+    if conn.closing.is_none() {
+        conn.closing = check_timeouts(conn, &state.limits, now);
+    }
+
+    conn.flush()?;
+
+    if conn.closing.is_none() {
+        return Ok(());
+    }
+
+    // the last replies like the AUTH_RESPONSE before a ban should make it out first
+    let closing_since = *conn.closing_since.get_or_insert(now);
+    match conn.write_buf.is_empty() || now.duration_since(closing_since) >= CLOSE_GRACE {
+        true => Err(conn.closing.take().expect("checked above")),
+        false => Ok(()),
+    }
+}
+
+fn check_timeouts(
+    conn: &RconStream,
+    limits: &RconLimits,
+    now: Instant,
+) -> Option<RconRequestError> {
+    match conn.identity {
+        None if now.duration_since(conn.connected_at) >= limits.auth_timeout => {
+            Some(RconRequestError::AuthTimeout(limits.auth_timeout))
+        }
+        Some(_)
+            if conn.subscription.is_none()
+                && now.duration_since(conn.last_activity) >= limits.idle_timeout =>
+        {
+            Some(RconRequestError::IdleTimeout(limits.idle_timeout))
+        }
+        _ => None,
     }
+}
+
+/// reads whatever the socket has for us into the connection's buffer
+///
+/// stops once a full packet could fit so a single client can't make the buffer grow forever in one frame
+fn fill_read_buffer(conn: &mut RconStream, max_packet_size: usize) -> Result<(), RconRequestError> {
+    let mut chunk = [0; READ_CHUNK_SIZE];
 
-    let client_id = i32::from_le_bytes(
-        buf.drain(..=3)
-            .collect::<Vec<u8>>()
-            .try_into()
-            .or(Err(RconRequestError::VecToArrayError))?,
-    );
-
-    let request_type = i32::from_le_bytes(
-        buf.drain(..=3)
-            .collect::<Vec<u8>>()
-            .try_into()
-            .or(Err(RconRequestError::VecToArrayError))?,
-    );
-
-    Ok((
-        client_id,
-        request_type,
-        String::from_utf8_lossy(&buf).to_string().replace('\0', ""),
-    )) // TODO: maybe use a struct
+    while !conn.eof && conn.read_buf.len() < max_packet_size + SIZE_FIELD_LEN {
+        match conn.stream.read(&mut chunk) {
+            Ok(0) => conn.eof = true,
+            Ok(bytes_read) => conn.read_buf.extend_from_slice(&chunk[..bytes_read]),
+            Err(err) => match err.kind() {
+                io::ErrorKind::WouldBlock => break,
+                io::ErrorKind::Interrupted => continue,
+                _ => Err(err)?,
+            },
+        }
+    }
+
+    Ok(())
 }
 
 fn parse_response(
     conn: &mut RconStream,
-    password: &str,
-    console: &mut ConsoleAccess,
-    client_id: i32,
-    request_type: i32,
-    content: String,
-) -> Result<RconResponse, RconRequestError> {
-    let response = match request_type {
-        SERVERDATA_AUTH => {
-            if content == password {
-                conn.auth = true;
-
-                log::info!("auth successful");
-
-                RconResponse {
-                    id: client_id,
-                    ty: SERVERDATA_AUTH_RESPONSE,
-                    content: String::new(),
-                }
-            } else {
-                log::warn!("auth failed");
-                conn.auth = false;
-
-                RconResponse {
-                    id: -1,
-                    ty: SERVERDATA_AUTH_RESPONSE,
-                    content: String::new(),
-                }
-            }
+    state: &mut ServerState,
+    request: RconPacket<'_>,
+    now: Instant,
+) -> Result<Vec<RconPacket<'static>>, RconRequestError> {
+    let ServerState {
+        accounts,
+        permissions,
+        throttle,
+        console,
+        executor,
+        limits,
+        ..
+    } = state;
+
+    let RconPacket {
+        id: client_id,
+        ty,
+        body: content,
+    } = request;
+
+    let responses = match ty {
+        PacketType::Auth => {
+            conn.identity = accounts.authenticate(&content);
+
+            let auth_id =
+                match &conn.identity {
+                    Some(identity) => {
+                        log::info!(
+                            "session {} ({}) authenticated as {identity}",
+                            conn.id,
+                            conn.addr
+                        );
+                        throttle.record_success(conn.addr.ip());
+
+                        client_id
+                    }
+                    None => {
+                        conn.failed_auths += 1;
+
+                        match throttle.record_failure(conn.addr.ip(), now) {
+                            AuthFailure::Banned(until) => {
+                                log::warn!(
+                                    "session {} ({}) failed to authenticate; banning {} for {:?}",
+                                    conn.id,
+                                    conn.addr,
+                                    conn.addr.ip(),
+                                    until.duration_since(now)
+                                );
+                                conn.closing = Some(RconRequestError::Banned);
+                            }
+                            AuthFailure::Delayed(until) => {
+                                log::warn!(
+                                    "session {} ({}) failed to authenticate; next attempt in {:?}",
+                                    conn.id,
+                                    conn.addr,
+                                    until.duration_since(now)
+                                );
+                            }
+                        }
+
+                        if conn.failed_auths >= throttle.policy().max_failures_per_connection {
+                            conn.closing = conn.closing.take().or(Some(
+                                RconRequestError::TooManyAuthFailures(conn.failed_auths),
+                            ));
+                        }
+
+                        -1
+                    }
+                };
+
+            // the spec has an empty RESPONSE_VALUE go out before every AUTH_RESPONSE
+            vec![
+                RconPacket::new(client_id, PacketType::ResponseValue, ""),
+                RconPacket::new(auth_id, PacketType::AuthResponse, ""),
+            ]
         }
-        SERVERDATA_EXECCOMMAND if content == "dumpconsole" => {
-            if !conn.auth {
-                Err(RconRequestError::InvalidClientID(client_id))?
+        PacketType::ExecCommand if split_first_word(&content).0 == "dumpconsole" => {
+            let identity = authenticated(conn, client_id)?;
+
+            // reading the console is as much a permission as running anything else
+            if let Err(denied) = permissions.check(identity.role, &tokenize(&content)) {
+                log::warn!("{identity} was refused : {denied}");
+
+                return Ok(split_response(client_id, &format!("{denied}\n")));
             }
-            log::info!("sending console dump");
 
-            RconResponse {
-                id: client_id,
-                ty: SERVERDATA_RESPONSE_VALUE,
-                content: console.get_last_console_output().iter().cloned().collect(),
+            let response = match DumpQuery::parse(split_first_word(&content).1) {
+                Ok(query) => {
+                    // half so the framing and anything else queued still fit
+                    let dump = console.history().dump(&query, limits.max_write_buffer / 2);
+                    log::info!(
+                        "sending {} lines of the console to {identity}",
+                        dump.lines.len()
+                    );
+                    dump.to_string()
+                }
+                Err(err) => format!("couldn't dump the console : {err}\n"),
+            };
+
+            split_response(client_id, &response)
+        }
+        PacketType::ExecCommand if split_first_word(&content).0 == "rcon_subscribe" => {
+            let identity = authenticated(conn, client_id)?;
+
+            if let Err(denied) = permissions.check(identity.role, &tokenize(&content)) {
+                log::warn!("{identity} was refused : {denied}");
+
+                return Ok(split_response(client_id, &format!("{denied}\n")));
             }
+
+            let response = match ConsoleFilter::parse(split_first_word(&content).1) {
+                Ok(filter) => {
+                    log::info!("{identity} subscribed to the console ({filter})");
+                    let response = format!("subscribed to console lines {filter}\n");
+                    conn.subscription = Some(Subscription {
+                        id: client_id,
+                        filter,
+                    });
+
+                    response
+                }
+                Err(err) => format!("couldn't subscribe : {err}\n"),
+            };
+
+            split_response(client_id, &response)
         }
-        SERVERDATA_EXECCOMMAND => {
-            if !conn.auth {
-                Err(RconRequestError::InvalidClientID(client_id))?
+        PacketType::ExecCommand if content.trim() == "rcon_unsubscribe" => {
+            let identity = authenticated(conn, client_id)?.clone();
+
+            let response = match conn.subscription.take() {
+                Some(_) => {
+                    log::info!("{identity} unsubscribed from the console");
+                    "unsubscribed from the console\n"
+                }
+                None => "wasn't subscribed to the console\n",
+            };
+
+            split_response(client_id, response)
+        }
+        PacketType::ExecCommand => {
+            let identity = authenticated(conn, client_id)?;
+
+            // every part is checked before anything runs so `kick x; quit` can't get a quit through
+            let mut commands = Vec::new();
+            for command in split_commands(&content) {
+                let Some(argv) = executor.tokenize(command) else {
+                    log::warn!("{identity} sent a command that couldn't be tokenized : {command}");
+
+                    return Ok(split_response(
+                        client_id,
+                        &format!("couldn't tokenize {command}\n"),
+                    ));
+                };
+
+                if let Err(denied) = permissions.check(identity.role, &argv) {
+                    log::warn!("{identity} was refused : {denied}");
+
+                    return Ok(split_response(client_id, &format!("{denied}\n")));
+                }
+
+                if !argv.is_empty() {
+                    commands.push((command, argv));
+                }
             }
-            log::info!("executing command : {content}");
-
-            let cmd = try_cstring(&content)
-                .unwrap_or_else(|_| to_cstring(content.replace('\0', "").as_str()));
-            let funcs = ENGINE_FUNCTIONS.wait();
-            unsafe {
-                (funcs.cbuf_add_text_type)(
-                    (funcs.cbuf_get_current_player)(),
-                    cmd.as_ptr(),
-                    CmdSource::Code,
+
+            let mut pending = PendingRequest::new(client_id, now);
+            for (command, argv) in commands {
+                let argv = format_argv(&argv);
+                log::info!("{identity} executing command : {argv}");
+
+                // only what's printed between the sentinels belongs to this command
+                let tag = console.next_tag(conn.id);
+                // half the write buffer so the framing and whatever else is queued still fit
+                let (begin, end) = console.start_capture(
+                    &tag,
+                    limits.max_write_buffer / 2,
+                    permissions.can_read_console(identity.role),
                 );
+                executor.echo(&begin);
+                executor.execute(command);
+                executor.echo(&end);
 
-                (funcs.cbuf_execute)() // execute the buffer rn since we want the results immediately
+                pending.commands.push((argv, tag));
             }
+            console.drain();
 
-            let mut response = String::new();
-            while let Some(console_out) = console.next_line_catpure() {
-                response += &console_out;
-            }
+            // the response goes out from `handle_connection` once the output is in
+            conn.pending = Some(pending);
+            Vec::new()
+        }
+        PacketType::ResponseValue => {
+            authenticated(conn, client_id)?;
+
+            // clients send an empty RESPONSE_VALUE after a command and wait for it to come back to know the output is over
+            vec![
+                RconPacket::new(client_id, PacketType::ResponseValue, ""),
+                RconPacket::new(client_id, PacketType::ResponseValue, MIRROR_TRAILER_CONTENT),
+            ]
+        }
+        PacketType::AuthResponse => Err(RconRequestError::InvalidRequestType(ty.to_i32()))?,
+    };
+
+    Ok(responses)
+}
 
-            RconResponse {
-                id: client_id,
-                ty: SERVERDATA_RESPONSE_VALUE,
-                content: response,
+fn split_first_word(content: &str) -> (&str, &str) {
+    let content = content.trim();
+    content
+        .split_once(char::is_whitespace)
+        .unwrap_or((content, ""))
+}
+
+fn authenticated(conn: &RconStream, client_id: i32) -> Result<&Identity, RconRequestError> {
+    conn.identity
+        .as_ref()
+        .ok_or(RconRequestError::Unauthenticated(client_id))
+}
+
+/// splits the output over as many RESPONSE_VALUE packets as needed without cutting a character in half
+fn split_response(client_id: i32, content: &str) -> Vec<RconPacket<'static>> {
+    let mut responses = Vec::new();
+    let mut rest = content;
+
+    loop {
+        let mut split_at = rest.len().min(MAX_CONTENT_SIZE);
+        while !rest.is_char_boundary(split_at) {
+            split_at -= 1;
+        }
+
+        // lines that fit in a packet aren't cut in half
+        if split_at < rest.len() {
+            if let Some(newline) = rest[..split_at].rfind('\n') {
+                split_at = newline + 1;
             }
         }
-        request_num => Err(RconRequestError::InvalidRequestType(request_num))?,
-    };
 
-    Ok(response)
+        let (chunk, remaining) = rest.split_at(split_at);
+        responses.push(RconPacket::new(
+            client_id,
+            PacketType::ResponseValue,
+            chunk.to_string(),
+        ));
+
+        if remaining.is_empty() {
+            break responses;
+        }
+        rest = remaining;
+    }
 }
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+pub struct AuthPolicy {
+    /// how long an ip has to wait after its first failed auth; doubles with every failure after that
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// failed auths on one connection before it gets dropped
+    pub max_failures_per_connection: u32,
+    /// failed auths from one ip before it gets banned
+    pub ban_threshold: u32,
+    pub ban_duration: Duration,
+    /// how long an ip has to go without failing before its failures are forgotten
+    pub failure_window: Duration,
+}
+
+impl Default for AuthPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_failures_per_connection: 3,
+            ban_threshold: 10,
+            ban_duration: Duration::from_secs(15 * 60),
+            failure_window: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// the ip has to wait until then before its next attempt is looked at
+    Delayed(Instant),
+    Banned(Instant),
+}
+
+#[derive(Debug)]
+struct FailureRecord {
+    failures: u32,
+    last_failure: Instant,
+    delayed_until: Instant,
+}
+
+/// tracks failed auths per ip to slow down and eventually ban password guessing
+#[derive(Debug, Default)]
+pub struct AuthThrottle {
+    policy: AuthPolicy,
+    failures: HashMap<IpAddr, FailureRecord>,
+    bans: HashMap<IpAddr, Instant>,
+}
+
+impl AuthThrottle {
+    pub fn new(policy: AuthPolicy) -> Self {
+        Self {
+            policy,
+            failures: HashMap::new(),
+            bans: HashMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &AuthPolicy {
+        &self.policy
+    }
+
+    pub fn is_banned(&self, ip: IpAddr, now: Instant) -> bool {
+        self.bans.get(&ip).is_some_and(|until| now < *until)
+    }
+
+    pub fn is_delayed(&self, ip: IpAddr, now: Instant) -> bool {
+        self.failures
+            .get(&ip)
+            .is_some_and(|record| now < record.delayed_until)
+    }
+
+    pub fn record_failure(&mut self, ip: IpAddr, now: Instant) -> AuthFailure {
+        let record = self.failures.entry(ip).or_insert(FailureRecord {
+            failures: 0,
+            last_failure: now,
+            delayed_until: now,
+        });
+
+        record.failures += 1;
+        record.last_failure = now;
+
+        let delay = self
+            .policy
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(record.failures - 1))
+            .min(self.policy.max_delay);
+        record.delayed_until = now + delay;
+
+        // the delay stays too so connections that were already open can't keep guessing
+        if record.failures >= self.policy.ban_threshold {
+            let until = now + self.policy.ban_duration;
+            _ = self.bans.insert(ip, until);
+
+            return AuthFailure::Banned(until);
+        }
+
+        AuthFailure::Delayed(record.delayed_until)
+    }
+
+    pub fn record_success(&mut self, ip: IpAddr) {
+        _ = self.failures.remove(&ip);
+    }
+
+    /// forgets expired bans and failures that are too old to matter
+    pub fn prune(&mut self, now: Instant) {
+        let window = self.policy.failure_window;
+
+        self.bans.retain(|_, until| now < *until);
+        self.failures.retain(|_, record| {
+            now < record.delayed_until || now.duration_since(record.last_failure) < window
+        });
+    }
+}
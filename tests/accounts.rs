@@ -0,0 +1,46 @@
+use r2rcon_rs::accounts::{Account, AccountError, Accounts, Role};
+
+#[test]
+fn accounts_need_usable_names_and_passwords() {
+    // names tell accounts apart so sharing a password is fine
+    assert!(Accounts::new(vec![
+        Account::new("a", "same", Role::Owner),
+        Account::new("b", "same", Role::Viewer),
+    ])
+    .is_ok());
+    assert!(matches!(
+        Accounts::new(vec![
+            Account::new("a", "one", Role::Owner),
+            Account::new("a", "two", Role::Viewer),
+        ]),
+        Err(AccountError::DuplicateName(_))
+    ));
+    assert!(matches!(
+        Accounts::new(vec![Account::new("a:b", "pw", Role::Owner)]),
+        Err(AccountError::InvalidName(_))
+    ));
+    assert!(matches!(
+        Accounts::new(vec![Account::new("a", "", Role::Owner)]),
+        Err(AccountError::EmptyPassword(_))
+    ));
+}
+
+#[test]
+fn logins_name_their_account() {
+    let accounts = Accounts::new(vec![Account::new("cat", "ownerpw", Role::Owner)]).unwrap();
+    assert_eq!(
+        accounts
+            .authenticate("cat:ownerpw")
+            .map(|identity| identity.role),
+        Some(Role::Owner)
+    );
+    // so adding a second account later doesn't change how everyone logs in
+    assert!(accounts.authenticate("ownerpw").is_none());
+    assert!(accounts.authenticate("dog:ownerpw").is_none());
+
+    // a lone password takes either form, even with a : in it
+    let accounts = Accounts::from_password("hunter:2");
+    assert!(accounts.authenticate("hunter:2").is_some());
+    assert!(accounts.authenticate("admin:hunter:2").is_some());
+    assert!(accounts.authenticate("admin:hunter").is_none());
+}
@@ -0,0 +1,110 @@
+use r2rcon_rs::args::{read_args_file, split_args, ArgError, RconArgs};
+use std::path::Path;
+
+fn parse(line: &str) -> Result<RconArgs, ArgError> {
+    RconArgs::parse(split_args(line).unwrap())
+}
+
+#[test]
+fn flags_take_values_either_way() {
+    let args = parse(
+        "NorthstarLauncher.exe -dedicated -rcon_ip_port 0.0.0.0:27015 +rcon_password=\"correct horse\" -multiple -rcon_allow=10.0.0.0/8,::1 -rcon_config cfg/r2rcon.toml",
+    )
+    .unwrap();
+
+    assert_eq!(
+        args,
+        RconArgs {
+            ip_port: Some("0.0.0.0:27015".to_string()),
+            password: Some("correct horse".to_string()),
+            allow: Some(vec!["10.0.0.0/8".parse().unwrap(), "::1".parse().unwrap()]),
+            config: Some("cfg/r2rcon.toml".into()),
+            ..Default::default()
+        }
+    );
+
+    // only a flag can't be a value
+    assert_eq!(
+        parse("-rcon_password -5 -rcon_password_file=-secret")
+            .unwrap()
+            .password
+            .as_deref(),
+        Some("-5")
+    );
+    assert_eq!(
+        parse("-rcon_password_file=-secret").unwrap().password_file,
+        Some("-secret".into())
+    );
+    assert_eq!(
+        parse("-port 1 +map mp_glitch").unwrap(),
+        RconArgs::default()
+    );
+
+    // a typo only gets a warning and a convar that happens to start with rcon_ isn't ours at all
+    assert_eq!(
+        parse("-rcon_pasword hunter2 +rcon_convar 1 -rcon_password=pw").unwrap(),
+        RconArgs {
+            password: Some("pw".to_string()),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn the_last_flag_wins() {
+    let args =
+        parse("-rcon_password first -rcon_ip_port 127.0.0.1:1 -rcon_password=second").unwrap();
+
+    assert_eq!(args.password.as_deref(), Some("second"));
+    assert_eq!(args.ip_port.as_deref(), Some("127.0.0.1:1"));
+}
+
+#[test]
+fn errors_name_the_flag() {
+    let err = |line| parse(line).unwrap_err().to_string();
+
+    assert!(
+        err("-rcon_password -rcon_ip_port 127.0.0.1:1").starts_with("-rcon_password needs a value")
+    );
+    assert!(err("-rcon_ip_port").starts_with("-rcon_ip_port needs a value"));
+    assert!(err("-rcon_password=").starts_with("-rcon_password needs a value"));
+    assert_eq!(
+        err("-rcon_deny 10.0.0.1,nope"),
+        "-rcon_deny: nope isn't an ip address"
+    );
+
+    assert_eq!(split_args("-rcon_password \"open"), None);
+}
+
+#[test]
+fn startup_args_file_comes_before_the_command_line() {
+    let dir = std::env::temp_dir().join(format!("r2rcon-args-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ns_startup_args_dedi.txt");
+    std::fs::write(
+        &path,
+        "-dedicated -multiple\n-rcon_ip_port 0.0.0.0:27015\r\n-rcon_password \"from file\"\n",
+    )
+    .unwrap();
+
+    let from_file = read_args_file(&path).unwrap();
+    let args = RconArgs::parse(
+        from_file
+            .into_iter()
+            .chain(["-rcon_password".to_string(), "from argv".to_string()]),
+    )
+    .unwrap();
+    assert_eq!(args.ip_port.as_deref(), Some("0.0.0.0:27015"));
+    assert_eq!(args.password.as_deref(), Some("from argv"));
+
+    assert!(read_args_file(&dir.join("missing.txt")).unwrap().is_empty());
+
+    std::fs::write(&path, "-rcon_password \"never closed\n").unwrap();
+    assert_eq!(
+        read_args_file(&path).unwrap_err().to_string(),
+        format!("{} has a quote that's never closed", path.display())
+    );
+    assert!(read_args_file(Path::new(&dir)).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
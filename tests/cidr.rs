@@ -0,0 +1,40 @@
+use r2rcon_rs::cidr::{parse_cidr_list, CidrError, IpCidr};
+use std::str::FromStr;
+
+#[test]
+fn cidrs_match_v4_and_v6_ranges() {
+    let lan: IpCidr = "10.8.0.77/16".parse().unwrap();
+    assert_eq!(lan.to_string(), "10.8.0.0/16");
+    assert!(lan.contains("10.8.255.1".parse().unwrap()));
+    assert!(!lan.contains("10.9.0.1".parse().unwrap()));
+    assert!(lan.contains("::ffff:10.8.1.1".parse().unwrap()));
+
+    let v6: IpCidr = "fd00::/8".parse().unwrap();
+    assert!(v6.contains("fd12:3456::1".parse().unwrap()));
+    assert!(!v6.contains("fe80::1".parse().unwrap()));
+    assert!(!v6.contains("10.8.0.1".parse().unwrap()));
+
+    assert!(IpCidr::from_str("0.0.0.0/0")
+        .unwrap()
+        .contains("1.2.3.4".parse().unwrap()));
+    assert_eq!(
+        parse_cidr_list("127.0.0.1, ::1").unwrap(),
+        [
+            IpCidr::from_str("127.0.0.1/32").unwrap(),
+            IpCidr::from_str("::1/128").unwrap()
+        ]
+    );
+
+    assert!(matches!(
+        IpCidr::from_str("10.0.0.0/33"),
+        Err(CidrError::PrefixTooLong { max: 32, .. })
+    ));
+    assert!(matches!(
+        IpCidr::from_str("10.0.0/8"),
+        Err(CidrError::InvalidAddress(_))
+    ));
+    assert!(matches!(
+        IpCidr::from_str("::/abc"),
+        Err(CidrError::InvalidPrefix(_))
+    ));
+}
@@ -0,0 +1,157 @@
+use r2rcon_rs::packet::{
+    decode_packets, Direction, PacketError, PacketType, RconPacket, MAX_CONTENT_SIZE,
+    MAX_PACKET_SIZE,
+};
+use std::borrow::Cow;
+
+fn round_trip(packet: RconPacket<'_>, direction: Direction) {
+    let encoded = packet.encode();
+
+    let (decoded, len) = RconPacket::decode(&encoded, direction, MAX_PACKET_SIZE)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(decoded, packet);
+    assert_eq!(len, encoded.len());
+}
+
+#[test]
+fn round_trip_every_packet_type() {
+    round_trip(
+        RconPacket::new(1, PacketType::Auth, "password"),
+        Direction::Request,
+    );
+    round_trip(
+        RconPacket::new(2, PacketType::ExecCommand, "status"),
+        Direction::Request,
+    );
+    round_trip(
+        RconPacket::new(-1, PacketType::AuthResponse, ""),
+        Direction::Response,
+    );
+    round_trip(
+        RconPacket::new(3, PacketType::ResponseValue, "hostname: test"),
+        Direction::Response,
+    );
+}
+
+#[test]
+fn round_trip_edge_bodies() {
+    round_trip(
+        RconPacket::new(i32::MAX, PacketType::ResponseValue, "\0\0\0\u{1}\0\0\0\0"),
+        Direction::Response,
+    );
+    round_trip(
+        RconPacket::new(i32::MIN, PacketType::ResponseValue, "ünïcödé ✓"),
+        Direction::Response,
+    );
+    round_trip(
+        RconPacket::new(4, PacketType::ResponseValue, "a".repeat(MAX_CONTENT_SIZE)),
+        Direction::Response,
+    );
+}
+
+#[test]
+fn encode_matches_spec_layout() {
+    assert_eq!(
+        RconPacket::new(7, PacketType::ExecCommand, "echo").encode(),
+        [
+            0x0e, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, b'e', b'c',
+            b'h', b'o', 0x00, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn shared_type_value_depends_on_direction() {
+    let encoded = RconPacket::new(1, PacketType::ExecCommand, "").encode();
+
+    let (request, _) = RconPacket::decode(&encoded, Direction::Request, MAX_PACKET_SIZE)
+        .unwrap()
+        .unwrap();
+    let (response, _) = RconPacket::decode(&encoded, Direction::Response, MAX_PACKET_SIZE)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(request.ty, PacketType::ExecCommand);
+    assert_eq!(response.ty, PacketType::AuthResponse);
+}
+
+#[test]
+fn decode_borrows_valid_utf8() {
+    let encoded = RconPacket::new(1, PacketType::ExecCommand, "status").encode();
+
+    let (packet, _) = RconPacket::decode(&encoded, Direction::Request, MAX_PACKET_SIZE)
+        .unwrap()
+        .unwrap();
+
+    assert!(matches!(packet.body, Cow::Borrowed("status")));
+}
+
+#[test]
+fn decode_waits_for_whole_packet() {
+    let encoded = RconPacket::new(1, PacketType::ExecCommand, "status").encode();
+
+    for len in 0..encoded.len() {
+        assert!(
+            RconPacket::decode(&encoded[..len], Direction::Request, MAX_PACKET_SIZE)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
+
+#[test]
+fn decode_packets_handles_pipelined_and_partial() {
+    let first = RconPacket::new(1, PacketType::ExecCommand, "status");
+    let second = RconPacket::new(2, PacketType::ResponseValue, "");
+    let third = RconPacket::new(3, PacketType::ExecCommand, "say hi");
+
+    let mut buf = [first.encode(), second.encode(), third.encode()].concat();
+    let partial = buf.split_off(buf.len() - 3);
+
+    assert_eq!(
+        decode_packets(&mut buf, Direction::Request, MAX_PACKET_SIZE).unwrap(),
+        [first, second]
+    );
+
+    buf.extend_from_slice(&partial);
+
+    assert_eq!(
+        decode_packets(&mut buf, Direction::Request, MAX_PACKET_SIZE).unwrap(),
+        [third]
+    );
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn decode_rejects_bad_packets() {
+    fn decode(buf: &[u8]) -> Result<Option<(RconPacket<'_>, usize)>, PacketError> {
+        RconPacket::decode(buf, Direction::Request, MAX_PACKET_SIZE)
+    }
+
+    assert!(matches!(
+        decode(&(-5i32).to_le_bytes()),
+        Err(PacketError::NegativeSize(-5))
+    ));
+    assert!(matches!(
+        decode(&9i32.to_le_bytes()),
+        Err(PacketError::TooSmall(9))
+    ));
+    assert!(matches!(
+        decode(&i32::MAX.to_le_bytes()),
+        Err(PacketError::TooLarge { .. })
+    ));
+    assert!(matches!(
+        decode(&[
+            0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, b'h', b'i',
+        ]),
+        Err(PacketError::MissingNullTerminator)
+    ));
+    assert!(matches!(
+        decode(&[
+            0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]),
+        Err(PacketError::InvalidType(9))
+    ));
+}
@@ -0,0 +1,101 @@
+//! a fake executor that answers commands from a table by printing into the console channel
+
+use parking_lot::Mutex;
+use r2rcon_rs::executor::CommandExecutor;
+use std::{
+    collections::HashMap,
+    sync::{mpsc, mpsc::Sender, Arc},
+    thread,
+    time::Duration,
+};
+
+/// answers commands from a table by printing into a console channel like the engine would
+///
+/// used to run the server without the game in tests
+pub struct ScriptedExecutor {
+    console: Sender<String>,
+    responses: HashMap<String, Vec<String>>,
+    late: HashMap<String, Duration>,
+    /// once a late command ran everything goes through here so the console stays in order
+    relay: Option<Sender<String>>,
+    executed: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptedExecutor {
+    pub fn new(console: Sender<String>) -> Self {
+        Self {
+            console,
+            responses: HashMap::new(),
+            late: HashMap::new(),
+            relay: None,
+            executed: Arc::default(),
+        }
+    }
+
+    pub fn with_response(
+        mut self,
+        command: impl Into<String>,
+        lines: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        _ = self.responses.insert(
+            command.into(),
+            lines.into_iter().map(|line| line.into()).collect(),
+        );
+        self
+    }
+
+    /// like `with_response` but the lines come from another thread after `delay`, the way
+    /// output from the Print hook or a map change would
+    ///
+    /// everything printed after it is held back as well
+    pub fn with_late_response(
+        mut self,
+        command: impl Into<String>,
+        lines: impl IntoIterator<Item = impl Into<String>>,
+        delay: Duration,
+    ) -> Self {
+        let command = command.into();
+        _ = self.late.insert(command.clone(), delay);
+        self.with_response(command, lines)
+    }
+
+    fn print(&self, line: String) {
+        _ = match &self.relay {
+            Some(relay) => relay.send(line),
+            None => self.console.send(line),
+        };
+    }
+
+    /// every command this executor ran, in order
+    pub fn executed(&self) -> Arc<Mutex<Vec<String>>> {
+        Arc::clone(&self.executed)
+    }
+}
+
+impl CommandExecutor for ScriptedExecutor {
+    fn execute(&mut self, command: &str) {
+        self.executed.lock().push(command.to_string());
+
+        if let (Some(delay), None) = (self.late.get(command), &self.relay) {
+            let (relay, relayed) = mpsc::channel();
+            let (console, delay) = (self.console.clone(), *delay);
+            _ = thread::spawn(move || {
+                thread::sleep(delay);
+                relayed.iter().for_each(|line| _ = console.send(line));
+            });
+            self.relay = Some(relay);
+        }
+
+        match self.responses.get(command) {
+            Some(lines) => lines.iter().for_each(|line| self.print(line.clone())),
+            None => {
+                let name = command.split_whitespace().next().unwrap_or_default();
+                self.print(format!("Unknown command \"{name}\"\n"));
+            }
+        }
+    }
+
+    fn echo(&mut self, text: &str) {
+        self.print(format!("{text}\n"));
+    }
+}
@@ -0,0 +1,244 @@
+//! a localhost harness that drives `RconServer` the way `runframe` does, with a fake console and executor
+
+#![allow(dead_code)]
+
+mod executor;
+
+pub use executor::ScriptedExecutor;
+
+use parking_lot::Mutex;
+use r2rcon_rs::{
+    accounts::Accounts,
+    packet::{decode_packets, Direction, PacketType, RconPacket, MAX_PACKET_SIZE},
+    rcon::RconServer,
+};
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+    sync::{
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const PASSWORD: &str = "passwrd";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct TestServer {
+    pub server: RconServer,
+    /// prints into the fake console like the game would
+    pub console: Sender<String>,
+    pub executed: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        Self::start_with(|executor| executor)
+    }
+
+    pub fn start_with(setup: impl FnOnce(ScriptedExecutor) -> ScriptedExecutor) -> Self {
+        Self::start_with_accounts(Accounts::from_password(PASSWORD), setup)
+    }
+
+    pub fn start_with_accounts(
+        accounts: Accounts,
+        setup: impl FnOnce(ScriptedExecutor) -> ScriptedExecutor,
+    ) -> Self {
+        let (console, console_recv) = mpsc::channel();
+        let executor = setup(ScriptedExecutor::new(console.clone()));
+        let executed = executor.executed();
+
+        let server = RconServer::try_new("127.0.0.1:0", accounts, console_recv, executor).unwrap();
+
+        Self {
+            server,
+            console,
+            executed,
+        }
+    }
+
+    pub fn frame(&mut self) {
+        self.server.run();
+    }
+
+    pub fn connect(&mut self) -> TestClient {
+        let stream = TcpStream::connect(self.server.local_addr().unwrap()).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        TestClient {
+            stream,
+            read_buf: Vec::new(),
+            received: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    /// connects and authenticates
+    pub fn login(&mut self) -> TestClient {
+        let mut client = self.connect();
+        assert!(client.auth(self, PASSWORD), "auth failed");
+        client
+    }
+}
+
+pub struct TestClient {
+    pub stream: TcpStream,
+    read_buf: Vec<u8>,
+    received: VecDeque<RconPacket<'static>>,
+    closed: bool,
+}
+
+impl TestClient {
+    pub fn send(&mut self, packet: &RconPacket<'_>) {
+        self.send_raw(&packet.encode());
+    }
+
+    pub fn send_raw(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).unwrap();
+        self.stream.flush().unwrap();
+    }
+
+    /// pulls whatever the server sent without waiting; returns false once the server hung up
+    fn poll(&mut self) -> bool {
+        let mut chunk = [0; 4096];
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(bytes_read) => {
+                    self.read_buf.extend_from_slice(&chunk[..bytes_read]);
+                    continue;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    self.closed = true
+                }
+                Err(err) => panic!("{err}"),
+            }
+
+            break !self.closed;
+        }
+    }
+
+    /// runs frames until `len` raw bytes came back or the server hung up
+    pub fn read_raw(&mut self, server: &mut TestServer, len: usize) -> Vec<u8> {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while self.read_buf.len() < len && !self.closed {
+            assert!(
+                Instant::now() < deadline,
+                "timed out after {:?}",
+                self.read_buf
+            );
+
+            server.frame();
+            self.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        self.read_buf
+            .drain(..len.min(self.read_buf.len()))
+            .collect()
+    }
+
+    /// runs frames until `count` packets came back
+    pub fn recv(&mut self, server: &mut TestServer, count: usize) -> Vec<RconPacket<'static>> {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while self.received.len() < count {
+            assert!(
+                Instant::now() < deadline,
+                "timed out after {:?}",
+                self.received
+            );
+            assert!(!self.closed, "disconnected after {:?}", self.received);
+
+            server.frame();
+            self.poll();
+            self.received.extend(
+                decode_packets(&mut self.read_buf, Direction::Response, MAX_PACKET_SIZE).unwrap(),
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        self.received.drain(..count).collect()
+    }
+
+    pub fn auth(&mut self, server: &mut TestServer, password: &str) -> bool {
+        self.send(&RconPacket::new(1, PacketType::Auth, password));
+
+        let packets = self.recv(server, 2);
+        assert_eq!(
+            packets[0],
+            RconPacket::new(1, PacketType::ResponseValue, "")
+        );
+        assert_eq!(packets[1].ty, PacketType::AuthResponse);
+
+        packets[1].id == 1
+    }
+
+    /// runs a command and collects its whole output using the mirrored empty packet trick
+    pub fn exec(
+        &mut self,
+        server: &mut TestServer,
+        id: i32,
+        command: &str,
+    ) -> Vec<RconPacket<'static>> {
+        let end_id = id.wrapping_add(1000);
+
+        self.send(&RconPacket::new(id, PacketType::ExecCommand, command));
+        self.send(&RconPacket::new(end_id, PacketType::ResponseValue, ""));
+
+        let mut output = Vec::new();
+        loop {
+            let packet = self.recv(server, 1).remove(0);
+            if packet.id == end_id {
+                break;
+            }
+            assert_eq!(packet.id, id);
+            assert_eq!(packet.ty, PacketType::ResponseValue);
+            output.push(packet);
+        }
+
+        // the trailer srcds sends after the mirrored packet
+        assert_eq!(self.recv(server, 1)[0].id, end_id);
+
+        output
+    }
+
+    pub fn exec_to_string(&mut self, server: &mut TestServer, id: i32, command: &str) -> String {
+        self.exec(server, id, command)
+            .into_iter()
+            .map(|packet| packet.body.into_owned())
+            .collect()
+    }
+
+    /// runs frames until the server hangs up; panics if it sends anything instead
+    pub fn assert_disconnected(&mut self, server: &mut TestServer) {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while !self.closed {
+            assert!(Instant::now() < deadline, "the server never hung up");
+
+            server.frame();
+            self.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(
+            self.read_buf.is_empty() && self.received.is_empty(),
+            "got {:?} {:?} instead of a disconnect",
+            self.received,
+            self.read_buf
+        );
+    }
+}
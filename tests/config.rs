@@ -0,0 +1,276 @@
+mod common;
+
+use common::ScriptedExecutor;
+use r2rcon_rs::{
+    accounts::Role,
+    args::RconArgs,
+    config::{Config, ConfigError, CONFIG_FILE_NAME},
+    history::HistoryLimits,
+    password::PasswordSource,
+};
+use std::{path::Path, sync::mpsc, time::Duration};
+
+const CONFIG: &str = r#"
+bind = ["127.0.0.1:0", "127.0.0.1:0"]
+
+[[accounts]]
+name = "cat"
+password = "ownerpw"
+role = "owner"
+
+[[accounts]]
+name = "mod"
+password = "$pbkdf2-sha256$i=1000,l=32$cjJyY29uLXRlc3Qtc2FsdA$g/cALJHclzExxPxZfd3cj8sTpAjtAPav/9xmeO6svMA"
+role = "Moderator"
+
+[limits]
+max_connections = 8
+idle_timeout_secs = 60
+
+[auth]
+ban_threshold = 5
+
+[history]
+max_lines = 2000
+
+[ip_filter]
+allow = ["127.0.0.0/8", "::1"]
+
+[permissions.moderator]
+allow = ["kick", "say", "status", "map mp_.*"]
+deny = ["kick cat"]
+
+[logging]
+level = "debug"
+
+[capture]
+print = false
+"#;
+
+fn parse_err(config: &str) -> String {
+    Config::parse(config)
+        .and_then(|config| config.resolve(&RconArgs::default(), None))
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn full_config_resolves() {
+    let settings = Config::parse(CONFIG)
+        .unwrap()
+        .resolve(&RconArgs::default(), None)
+        .unwrap();
+
+    assert_eq!(settings.bind, ["127.0.0.1:0", "127.0.0.1:0"]);
+    assert_eq!(
+        settings
+            .accounts
+            .iter()
+            .map(|account| (account.name.as_str(), account.role))
+            .collect::<Vec<_>>(),
+        [("cat", Role::Owner), ("mod", Role::Moderator)]
+    );
+    assert_eq!(settings.password_source, None);
+    assert_eq!(settings.limits.max_connections, 8);
+    assert_eq!(settings.limits.max_connections_per_ip, 4);
+    assert_eq!(settings.limits.idle_timeout, Duration::from_secs(60));
+    assert_eq!(settings.auth_policy.ban_threshold, 5);
+    assert_eq!(settings.history.max_lines, 2000);
+    assert_eq!(
+        settings.history.max_bytes,
+        HistoryLimits::default().max_bytes
+    );
+    assert_eq!(settings.ip_filter.allow.len(), 2);
+    assert!(settings.ip_filter.deny.is_empty());
+    assert_eq!(settings.log_level, log::LevelFilter::Debug);
+    assert!(settings.capture.write_console && !settings.capture.print);
+
+    let check = |argv: &[&str]| {
+        settings
+            .permissions
+            .check(
+                Role::Moderator,
+                &argv.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(),
+            )
+            .is_ok()
+    };
+    assert!(check(&["map", "mp_glitch"]));
+    assert!(check(&["kick", "dog"]));
+    assert!(!check(&["kick", "cat"]));
+    assert!(settings
+        .permissions
+        .check(Role::Owner, &["quit".to_string()])
+        .is_ok());
+
+    let summary = settings.to_string();
+    assert!(summary.contains("listening on 127.0.0.1:0, 127.0.0.1:0"));
+    assert!(summary.contains("accounts: cat (owner), mod (moderator)"));
+    assert!(summary.contains("moderator can run kick, say, status, map mp_.*; except kick cat"));
+    assert!(summary.contains("capturing the console from WriteConsoleA"));
+    assert!(!summary.contains("ownerpw") && !summary.contains("pbkdf2"));
+}
+
+#[test]
+fn command_line_wins_over_the_file() {
+    let args = RconArgs {
+        ip_port: Some("127.0.0.1:27015".to_string()),
+        password: Some("frompw".to_string()),
+        deny: Some(vec!["10.0.0.0/8".parse().unwrap()]),
+        ..Default::default()
+    };
+
+    let with_password =
+        "bind = [\"127.0.0.1:0\"]\npassword = \"filepw\"\n[ip_filter]\nallow = [\"127.0.0.0/8\", \"::1\"]";
+    let settings = Config::parse(with_password)
+        .unwrap()
+        .resolve(&args, None)
+        .unwrap();
+    assert_eq!(settings.bind, ["127.0.0.1:27015"]);
+    assert_eq!(settings.password_source, Some(PasswordSource::Arg));
+    assert_eq!(settings.accounts.iter().count(), 1);
+    assert_eq!(settings.ip_filter.allow.len(), 2);
+    assert_eq!(settings.ip_filter.deny.len(), 1);
+    assert!(!settings.to_string().contains("frompw"));
+
+    let settings = Config::parse(with_password)
+        .unwrap()
+        .resolve(&args, Some("fromenv".to_string()))
+        .unwrap();
+    assert_eq!(settings.password_source, Some(PasswordSource::Env));
+
+    // accounts aren't swapped for a single owner behind anyone's back
+    assert!(matches!(
+        Config::parse(CONFIG).unwrap().resolve(&args, None),
+        Err(ConfigError::AccountsOverridden(PasswordSource::Arg))
+    ));
+    assert!(matches!(
+        Config::parse(CONFIG)
+            .unwrap()
+            .resolve(&RconArgs::default(), Some("fromenv".to_string())),
+        Err(ConfigError::AccountsOverridden(PasswordSource::Env))
+    ));
+
+    // no file at all works the same as before there were config files
+    let settings = Config::default().resolve(&args, None).unwrap();
+    assert_eq!(settings.bind, ["127.0.0.1:27015"]);
+    assert!(settings.accounts.authenticate("frompw").is_some());
+}
+
+#[test]
+fn invalid_configs_are_explained() {
+    assert!(parse_err("bnd = []").contains("unknown field `bnd`"));
+    assert!(
+        parse_err("bind = [\"127.0.0.1:0\"]\n[ip_filter]\nallow = [\"10.0.0.0/33\"]")
+            .contains("too long for 10.0.0.0")
+    );
+    assert!(parse_err(
+        "bind = [\"127.0.0.1:0\"]\n[[accounts]]\nname = \"a\"\npassword = \"b\"\nrole = \"admin\""
+    )
+    .contains("admin isn't a role"));
+    assert!(parse_err("password = \"$argon2id$v=19\"").contains("missing its salt or hash"));
+    assert!(
+        parse_err("bind = [\"127.0.0.1:0\"]\n[permissions.viewer]\nallow = [\"say (\"]")
+            .contains("invalid argument pattern for say")
+    );
+    assert!(parse_err("[logging]\nlevel = \"loud\"").contains("line 2"));
+
+    assert!(matches!(
+        Config::parse("password = \"pw\"")
+            .unwrap()
+            .resolve(&RconArgs::default(), None),
+        Err(ConfigError::NoBindAddress)
+    ));
+    assert!(matches!(
+        Config::parse("bind = [\"127.0.0.1:0\"]")
+            .unwrap()
+            .resolve(&RconArgs::default(), None),
+        Err(ConfigError::NoCredentials)
+    ));
+    assert!(matches!(
+        Config::parse(&format!("password = \"pw\"\n{CONFIG}"))
+            .unwrap()
+            .resolve(&RconArgs::default(), None),
+        Err(ConfigError::ConflictingCredentials)
+    ));
+    assert_eq!(
+        parse_err("bind = [\"127.0.0.1:0\"]\npassword = \"pw\"\n[limits]\nmax_connections = 0"),
+        "limits.max_connections has to be more than 0"
+    );
+    assert_eq!(
+        parse_err("bind = [\"127.0.0.1:0\"]\npassword = \"pw\"\n[limits]\nmax_packet_size = 0"),
+        "limits.max_packet_size has to be at least 4096 but got 0"
+    );
+    assert_eq!(
+        parse_err("bind = [\"127.0.0.1:0\"]\npassword = \"pw\"\n[limits]\nmax_write_buffer = 4096"),
+        "limits.max_write_buffer has to be at least 4100 but got 4096"
+    );
+}
+
+#[test]
+fn config_is_found_from_the_arg_or_next_to_the_plugin() {
+    let dir = std::env::temp_dir().join(format!("r2rcon-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    assert_eq!(Config::find(None, Some(&dir)), None);
+    assert_eq!(Config::find(None, None), None);
+
+    let path = dir.join(CONFIG_FILE_NAME);
+    std::fs::write(&path, CONFIG).unwrap();
+    assert_eq!(Config::find(None, Some(&dir)), Some(path.clone()));
+    assert_eq!(
+        Config::find(Some(Path::new("elsewhere.toml")), Some(&dir)),
+        Some("elsewhere.toml".into())
+    );
+
+    let settings = Config::load(&path)
+        .unwrap()
+        .resolve(&RconArgs::default(), None)
+        .unwrap();
+    assert!(settings
+        .to_string()
+        .starts_with(&format!("rcon config from {}", path.display())));
+
+    assert!(matches!(
+        Config::load(&dir.join("missing.toml")),
+        Err(ConfigError::Unreadable { .. })
+    ));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn password_files_are_found_next_to_the_config() {
+    let dir = std::env::temp_dir().join(format!("r2rcon-password-file-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("rcon_password"), "secret\n").unwrap();
+
+    let path = dir.join(CONFIG_FILE_NAME);
+    std::fs::write(
+        &path,
+        "bind = [\"127.0.0.1:0\"]\npassword_file = \"rcon_password\"",
+    )
+    .unwrap();
+
+    let settings = Config::load(&path)
+        .unwrap()
+        .resolve(&RconArgs::default(), None)
+        .unwrap();
+    assert!(settings.accounts.authenticate("secret").is_some());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn settings_start_a_server_on_every_address() {
+    let settings = Config::parse(CONFIG)
+        .unwrap()
+        .resolve(&RconArgs::default(), None)
+        .unwrap();
+    let (_console, console_recv) = mpsc::channel();
+    let (executor_console, _) = mpsc::channel();
+
+    let server = settings
+        .start_server(console_recv, ScriptedExecutor::new(executor_console))
+        .unwrap();
+    assert_ne!(server.local_addr().unwrap().port(), 0);
+}
@@ -0,0 +1,163 @@
+//! plays the byte sequences from the valve source rcon protocol spec against `RconServer`
+//!
+//! https://developer.valvesoftware.com/wiki/Source_RCON_Protocol
+
+mod common;
+
+use common::TestServer;
+
+// size 17, id 1, SERVERDATA_AUTH, "passwrd"
+const AUTH_REQUEST: [u8; 21] = [
+    0x11, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, b'p', b'a', b's', b's',
+    b'w', b'r', b'd', 0x00, 0x00,
+];
+
+// size 17, id 1, SERVERDATA_AUTH, "wrongpw"
+const BAD_AUTH_REQUEST: [u8; 21] = [
+    0x11, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, b'w', b'r', b'o', b'n',
+    b'g', b'p', b'w', 0x00, 0x00,
+];
+
+// size 10, id 1, SERVERDATA_RESPONSE_VALUE, ""
+const EMPTY_RESPONSE_VALUE: [u8; 14] = [
+    0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// size 10, id 1, SERVERDATA_AUTH_RESPONSE, ""
+const AUTH_RESPONSE: [u8; 14] = [
+    0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// size 10, id -1, SERVERDATA_AUTH_RESPONSE, ""
+const FAILED_AUTH_RESPONSE: [u8; 14] = [
+    0x0a, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// size 10, id 2, SERVERDATA_RESPONSE_VALUE, ""
+const MIRROR_REQUEST: [u8; 14] = [
+    0x0a, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// size 18, id 2, SERVERDATA_RESPONSE_VALUE, 0x00000001 0x00000000
+const MIRROR_TRAILER: [u8; 22] = [
+    0x12, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn auth_success_sends_empty_response_value_then_auth_response() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    client.send_raw(&AUTH_REQUEST);
+
+    assert_eq!(
+        client.read_raw(&mut server, 28),
+        [EMPTY_RESPONSE_VALUE, AUTH_RESPONSE].concat()
+    );
+}
+
+#[test]
+fn auth_failure_answers_with_id_minus_one() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    client.send_raw(&BAD_AUTH_REQUEST);
+
+    assert_eq!(
+        client.read_raw(&mut server, 28),
+        [EMPTY_RESPONSE_VALUE, FAILED_AUTH_RESPONSE].concat()
+    );
+}
+
+#[test]
+fn auth_split_across_segments() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    for byte in AUTH_REQUEST {
+        client.send_raw(&[byte]);
+        server.frame();
+    }
+
+    assert_eq!(
+        client.read_raw(&mut server, 28),
+        [EMPTY_RESPONSE_VALUE, AUTH_RESPONSE].concat()
+    );
+}
+
+#[test]
+fn pipelined_auth_and_mirrored_empty_packet() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    client.send_raw(&[AUTH_REQUEST.as_slice(), &MIRROR_REQUEST].concat());
+
+    assert_eq!(
+        client.read_raw(&mut server, 64),
+        [
+            EMPTY_RESPONSE_VALUE.as_slice(),
+            &AUTH_RESPONSE,
+            &MIRROR_REQUEST,
+            &MIRROR_TRAILER
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn mirror_before_auth_disconnects() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    client.send_raw(&MIRROR_REQUEST);
+
+    client.assert_disconnected(&mut server);
+}
+
+#[test]
+fn oversized_packet_disconnects() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    // size 0x7fffffff, the server shouldn't wait for or allocate any of it
+    client.send_raw(&[0xff, 0xff, 0xff, 0x7f]);
+
+    client.assert_disconnected(&mut server);
+}
+
+#[test]
+fn negative_packet_size_disconnects() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    client.send_raw(&[0xff, 0xff, 0xff, 0xff]);
+
+    client.assert_disconnected(&mut server);
+}
+
+#[test]
+fn undersized_packet_disconnects() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    // size 8, id 1, SERVERDATA_AUTH and no body at all
+    client.send_raw(&[
+        0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+    ]);
+
+    client.assert_disconnected(&mut server);
+}
+
+#[test]
+fn missing_null_terminator_disconnects() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    // size 10, id 1, SERVERDATA_AUTH, "pw" with no terminators
+    client.send_raw(&[
+        0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, b'p', b'w',
+    ]);
+
+    client.assert_disconnected(&mut server);
+}
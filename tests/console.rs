@@ -0,0 +1,84 @@
+use r2rcon_rs::console::ConsoleAccess;
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+fn texts(console: &ConsoleAccess) -> Vec<&str> {
+    console
+        .history()
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect()
+}
+
+#[test]
+fn captures_are_cut_off_at_their_size() {
+    let (sender, receiver) = mpsc::channel();
+    let mut console = ConsoleAccess::new(receiver);
+
+    let (begin, end) = console.start_capture("cvarlist", 10, true);
+    for line in [begin, "12345".to_string(), "67890".to_string(), end] {
+        sender.send(format!("{line}\n")).unwrap();
+    }
+    console.drain();
+
+    assert_eq!(
+        console.finish_capture("cvarlist", Instant::now()),
+        "12345\n... cut off after 10 bytes\n"
+    );
+    // the history still has all of it
+    assert_eq!(texts(&console), ["12345\n", "67890\n"]);
+}
+
+#[test]
+fn abandoned_captures_are_forgotten() {
+    let (sender, receiver) = mpsc::channel();
+    let mut console = ConsoleAccess::new(receiver);
+    let now = Instant::now();
+
+    let quit = console.next_tag(1);
+    let (begin, end) = console.start_capture(&quit, 1024, true);
+    sender.send(format!("{begin}\n")).unwrap();
+    console.drain();
+    assert_eq!(console.finish_capture(&quit, now), "");
+
+    // a late end sentinel is still swallowed while the capture is kept
+    let status = console.next_tag(1);
+    let (_, other_end) = console.start_capture(&status, 1024, true);
+    _ = console.finish_capture(&status, now);
+    sender.send(format!("{other_end}\n")).unwrap();
+    console.drain();
+    assert!(texts(&console).is_empty());
+
+    // and once it's gone too so the key never shows up in a dump
+    console.prune_captures(now + Duration::from_secs(1), Duration::from_millis(500));
+    sender.send("after\n".to_string()).unwrap();
+    sender.send(format!("{end}\n")).unwrap();
+    console.drain();
+    assert_eq!(texts(&console), ["after\n"]);
+    assert_eq!(console.take_fresh_lines(), ["after\n"]);
+}
+
+#[test]
+fn coloured_sentinels_still_match() {
+    let (sender, receiver) = mpsc::channel();
+    let mut console = ConsoleAccess::new(receiver);
+
+    let tag = console.next_tag(1);
+    let (begin, end) = console.start_capture(&tag, 1024, true);
+    for line in [
+        format!("\x1b[0m{begin}\x1b[0m\n"),
+        "\x1b[31mhostname: test server\x1b[0m\n".to_string(),
+        format!("{end}\x1b[0m\r\n"),
+    ] {
+        sender.send(line).unwrap();
+    }
+    console.drain();
+
+    assert_eq!(
+        console.finish_capture(&tag, Instant::now()),
+        "\x1b[31mhostname: test server\x1b[0m\n"
+    );
+    assert_eq!(texts(&console), ["\x1b[31mhostname: test server\x1b[0m\n"]);
+}
@@ -0,0 +1,141 @@
+use r2rcon_rs::history::{ConsoleHistory, ConsoleLine, DumpQuery, HistoryLimits};
+
+fn texts<'a>(lines: impl Iterator<Item = &'a ConsoleLine>) -> Vec<&'a str> {
+    lines.map(|line| line.text.as_str()).collect()
+}
+
+#[test]
+fn oldest_lines_go_first() {
+    let mut history = ConsoleHistory::new(HistoryLimits {
+        max_lines: 3,
+        max_bytes: 1024,
+    });
+
+    for i in 0..5 {
+        assert_eq!(history.push(format!("line {i}\n")), i);
+    }
+    assert_eq!(texts(history.iter()), ["line 2\n", "line 3\n", "line 4\n"]);
+    assert_eq!(history.bytes(), 21);
+
+    // the byte budget is separate from the line one
+    history.set_limits(HistoryLimits {
+        max_lines: 100,
+        max_bytes: 14,
+    });
+    assert_eq!(texts(history.iter()), ["line 3\n", "line 4\n"]);
+
+    // a line bigger than the whole budget still stays until the next one
+    _ = history.push("x".repeat(100));
+    assert_eq!(history.len(), 1);
+    _ = history.push("line 6\n".to_string());
+    assert_eq!(texts(history.iter()), ["line 6\n"]);
+}
+
+#[test]
+fn since_picks_up_where_a_client_left_off() {
+    let mut history = ConsoleHistory::new(HistoryLimits {
+        max_lines: 4,
+        ..Default::default()
+    });
+    assert_eq!(history.oldest_seq(), 0);
+    assert_eq!(history.since(0).count(), 0);
+
+    for i in 0..6 {
+        _ = history.push(format!("line {i}\n"));
+    }
+    assert_eq!(history.oldest_seq(), 2);
+    assert_eq!(history.next_seq(), 6);
+
+    assert_eq!(texts(history.since(4)), ["line 4\n", "line 5\n"]);
+    assert_eq!(history.since(6).count(), 0);
+    assert_eq!(history.since(60).count(), 0);
+
+    // asking from before the oldest kept line gets everything there is
+    let lines = history.since(0).collect::<Vec<_>>();
+    assert_eq!(lines[0].seq, history.oldest_seq());
+    assert!(lines.windows(2).all(|pair| pair[1].seq == pair[0].seq + 1));
+    assert!(lines.windows(2).all(|pair| pair[1].time >= pair[0].time));
+}
+
+#[test]
+fn dumps_page_through_filtered_lines() {
+    let mut history = ConsoleHistory::new(HistoryLimits::default());
+    for i in 0..10 {
+        let tag = if i % 2 == 0 { "[info]" } else { "[debug]" };
+        _ = history.push(format!("{tag} line {i}\n"));
+    }
+    let dump = |args: &str| {
+        history
+            .dump(&DumpQuery::parse(args).unwrap(), usize::MAX)
+            .to_string()
+    };
+    // the seq to poll from next, whatever the filters left out
+    let end = "dumpconsole: that's all; continue with since 10\n";
+
+    assert_eq!(
+        dump("last 2"),
+        format!("[info] line 8\n[debug] line 9\n{end}")
+    );
+    assert_eq!(
+        dump("last 2 exclude debug"),
+        format!("[info] line 6\n[info] line 8\n{end}")
+    );
+    assert_eq!(
+        dump("include \"line [0-2]$\""),
+        format!("[info] line 0\n[debug] line 1\n[info] line 2\n{end}")
+    );
+
+    assert_eq!(
+        dump("since 3 limit 2 include info"),
+        "[info] line 4\n[info] line 6\ndumpconsole: there's more; continue with page 8\n"
+    );
+    assert_eq!(
+        dump("since 3 limit 2 include info page 8"),
+        format!("[info] line 8\n{end}")
+    );
+
+    // the first page of the last few starts at the oldest of them
+    let page = history.dump(&DumpQuery::parse("last 5 limit 3").unwrap(), usize::MAX);
+    assert_eq!(page.lines[0].seq, 5);
+    assert_eq!(page.next_page, Some(8));
+
+    let err = |args| DumpQuery::parse(args).unwrap_err().to_string();
+    assert_eq!(err("last"), "last needs a value");
+    assert_eq!(err("last lots"), "last takes a number, not lots");
+    assert!(err("include (").starts_with("invalid pattern for include"));
+    assert!(err("first 2").starts_with("first isn't a dumpconsole option"));
+}
+
+#[test]
+fn dumps_say_when_lines_were_missed() {
+    let mut history = ConsoleHistory::new(HistoryLimits {
+        max_lines: 2,
+        ..Default::default()
+    });
+    for i in 0..5 {
+        _ = history.push(format!("line {i}"));
+    }
+
+    assert_eq!(
+        history
+            .dump(&DumpQuery::parse("since 1").unwrap(), usize::MAX)
+            .to_string(),
+        "dumpconsole: lines 1 to 2 aren't kept anymore\nline 3\nline 4\n\
+         dumpconsole: that's all; continue with since 5\n"
+    );
+    assert_eq!(
+        history
+            .dump(&DumpQuery::parse("since_time 0").unwrap(), usize::MAX)
+            .to_string(),
+        "line 3\nline 4\ndumpconsole: that's all; continue with since 5\n"
+    );
+    assert_eq!(
+        history
+            .dump(
+                &DumpQuery::parse("since_time 99999999999").unwrap(),
+                usize::MAX
+            )
+            .to_string(),
+        "dumpconsole: that's all; continue with since 5\n"
+    );
+}
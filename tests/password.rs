@@ -0,0 +1,105 @@
+use r2rcon_rs::password::{resolve_password, Password, PasswordError, PasswordSource};
+
+/// cheap hashes of `ownerpw` and `modpw`
+const OWNERPW_ARGON2: &str =
+    "$argon2id$v=19$m=256,t=1,p=1$cjJyY29uLXRlc3Qtc2FsdA$gxSfwiWXj4bbvnvhnPcuPpWgdAN/0x1pAeljAGQm3EQ";
+const MODPW_PBKDF2: &str =
+    "$pbkdf2-sha256$i=1000,l=32$cjJyY29uLXRlc3Qtc2FsdA$g/cALJHclzExxPxZfd3cj8sTpAjtAPav/9xmeO6svMA";
+
+#[test]
+fn passwords_verify_plain_and_hashed() {
+    let plain: Password = "hunter2".parse().unwrap();
+    assert!(plain.verify("hunter2"));
+    assert!(!plain.verify("hunter"));
+    assert!(!plain.verify("hunter22"));
+    assert!(!plain.verify(""));
+
+    let argon2: Password = OWNERPW_ARGON2.parse().unwrap();
+    assert!(matches!(argon2, Password::Hash(_)));
+    assert!(argon2.verify("ownerpw"));
+    assert!(!argon2.verify("modpw"));
+
+    let pbkdf2: Password = MODPW_PBKDF2.parse().unwrap();
+    assert!(pbkdf2.verify("modpw"));
+    assert!(!pbkdf2.verify("ownerpw"));
+
+    // the secret never ends up in logs through debug formatting
+    assert_eq!(format!("{plain:?}"), "Plain(..)");
+    assert_eq!(format!("{argon2:?}"), "Hash(argon2id)");
+
+    assert!(matches!(
+        "$argon2id$v=19$m=256,t=1,p=1$c!!$aaa".parse::<Password>(),
+        Err(PasswordError::InvalidHash(_))
+    ));
+    assert!(matches!(
+        "$argon2id$v=19$m=256,t=1,p=1".parse::<Password>(),
+        Err(PasswordError::Incomplete)
+    ));
+    assert!(matches!(
+        Password::hash("$scrypt$ln=4,r=8,p=1$cjJyY29uLXRlc3Qtc2FsdA$gxSfwiWXj4bbvnvhnPcuPpWgdAN/0x1pAeljAGQm3EQ"),
+        Err(PasswordError::UnsupportedAlgorithm(_))
+    ));
+
+    // anything that would stall the frame on every auth is refused up front
+    assert!(matches!(
+        OWNERPW_ARGON2
+            .replace("m=256,t=1", "m=65536,t=3")
+            .parse::<Password>(),
+        Err(PasswordError::TooExpensive(_))
+    ));
+    assert!(matches!(
+        MODPW_PBKDF2
+            .replace("i=1000", "i=600000")
+            .parse::<Password>(),
+        Err(PasswordError::TooExpensive(_))
+    ));
+}
+
+#[test]
+fn password_sources_have_a_precedence() {
+    let dir = std::env::temp_dir().join(format!("r2rcon-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("rcon_password");
+    std::fs::write(&file, "from file\r\n").unwrap();
+    let empty = dir.join("empty");
+    std::fs::write(&empty, "\n").unwrap();
+
+    let source = |file, env: Option<&str>, arg| {
+        resolve_password(file, env.map(str::to_string), arg)
+            .unwrap()
+            .map(|(password, source)| {
+                assert!(password.verify(match source {
+                    PasswordSource::File(_) => "from file",
+                    PasswordSource::Env => "from env",
+                    PasswordSource::Arg => "from arg",
+                    PasswordSource::Config => unreachable!(),
+                }));
+                source
+            })
+    };
+
+    assert_eq!(
+        source(Some(&file), Some("from env"), Some("from arg")),
+        Some(PasswordSource::File(file.clone()))
+    );
+    assert_eq!(
+        source(None, Some("from env"), Some("from arg")),
+        Some(PasswordSource::Env)
+    );
+    assert_eq!(
+        source(None, Some(""), Some("from arg")),
+        Some(PasswordSource::Arg)
+    );
+    assert_eq!(source(None, None, None), None);
+
+    assert!(matches!(
+        resolve_password(Some(&dir.join("missing")), None, Some("from arg")),
+        Err(PasswordError::UnreadableFile { .. })
+    ));
+    assert!(matches!(
+        resolve_password(Some(&empty), None, None),
+        Err(PasswordError::EmptyFile(_))
+    ));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,995 @@
+mod common;
+
+use common::{TestServer, PASSWORD};
+use r2rcon_rs::{
+    accounts::{Account, Accounts, Role},
+    cidr::{parse_cidr_list, IpFilter},
+    console::Severity,
+    packet::{PacketType, RconPacket, MAX_CONTENT_SIZE},
+    password::Password,
+    permissions::{CommandRule, Permissions, RoleRules},
+    rcon::RconLimits,
+    throttle::AuthPolicy,
+};
+use std::{
+    net::Shutdown,
+    time::{Duration, Instant},
+};
+
+fn quick_auth_policy() -> AuthPolicy {
+    AuthPolicy {
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+        max_failures_per_connection: 2,
+        ban_threshold: 3,
+        ban_duration: Duration::from_secs(60),
+        failure_window: Duration::from_secs(60),
+    }
+}
+
+fn team_accounts() -> Accounts {
+    Accounts::new(vec![
+        Account::new("cat", "ownerpw", Role::Owner),
+        Account::new("mod", "modpw", Role::Moderator),
+        Account::new("statsbot", "botpw", Role::Viewer),
+    ])
+    .unwrap()
+}
+
+#[test]
+fn auth_success_and_failure() {
+    let mut server = TestServer::start();
+
+    assert!(server.connect().auth(&mut server, PASSWORD));
+    assert!(!server.connect().auth(&mut server, "not the password"));
+}
+
+#[test]
+fn failed_auth_can_be_retried() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    assert!(!client.auth(&mut server, "wrong"));
+    assert!(client.auth(&mut server, PASSWORD));
+}
+
+#[test]
+fn exec_returns_command_output() {
+    let mut server = TestServer::start_with(|executor| {
+        executor.with_response("status", ["hostname: test server\n", "map: mp_glitch\n"])
+    });
+    let mut client = server.login();
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 5, "status"),
+        "] \"status\"\nhostname: test server\nmap: mp_glitch\n"
+    );
+    assert_eq!(*server.executed.lock(), ["status"]);
+}
+
+#[test]
+fn exec_unknown_command() {
+    let mut server = TestServer::start();
+    let mut client = server.login();
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 5, "not_a_command 1"),
+        "] \"not_a_command\" \"1\"\nUnknown command \"not_a_command\"\n"
+    );
+}
+
+#[test]
+fn long_output_is_split_over_packets() {
+    let line = "é".repeat(1000) + "\n";
+    let lines = vec![line.clone(); 5];
+    let mut server = TestServer::start_with(|executor| executor.with_response("cvarlist", lines));
+    let mut client = server.login();
+
+    let packets = client.exec(&mut server, 9, "cvarlist");
+
+    assert!(packets.len() > 1);
+    assert!(packets
+        .iter()
+        .all(|packet| packet.body.len() <= MAX_CONTENT_SIZE));
+    assert_eq!(
+        packets
+            .into_iter()
+            .map(|packet| packet.body.into_owned())
+            .collect::<String>(),
+        format!("] \"cvarlist\"\n{}", line.repeat(5))
+    );
+}
+
+#[test]
+fn dumpconsole_returns_captured_lines() {
+    let mut server = TestServer::start();
+    let mut client = server.login();
+
+    server.console.send("first line\n".to_string()).unwrap();
+    server.console.send("second line\n".to_string()).unwrap();
+    server.frame();
+
+    let dump = client.exec_to_string(&mut server, 3, "dumpconsole");
+
+    assert!(dump.contains("first line\n"));
+    assert!(dump.contains("second line\n"));
+    assert!(server.executed.lock().is_empty());
+}
+
+#[test]
+fn exec_without_auth_disconnects() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    client.send(&RconPacket::new(1, PacketType::ExecCommand, "status"));
+
+    client.assert_disconnected(&mut server);
+    assert!(server.executed.lock().is_empty());
+}
+
+#[test]
+fn malformed_packet_only_drops_that_client() {
+    let mut server = TestServer::start_with(|executor| executor.with_response("status", ["ok\n"]));
+    let mut good = server.login();
+    let mut bad = server.login();
+
+    bad.send_raw(&[0x03, 0x00, 0x00, 0x00, 0x01]);
+    bad.assert_disconnected(&mut server);
+
+    assert_eq!(
+        good.exec_to_string(&mut server, 2, "status"),
+        "] \"status\"\nok\n"
+    );
+    assert_eq!(server.server.connection_count(), 1);
+}
+
+#[test]
+fn half_closed_clients_still_get_their_replies() {
+    let line = "x".repeat(999) + "\n";
+    let lines = vec![line.clone(); 400];
+    let mut server = TestServer::start_with(|executor| executor.with_response("cvarlist", lines));
+    let mut client = server.connect();
+
+    // like `nc -N`: send everything then shut down the writing side
+    client.send(&RconPacket::new(1, PacketType::Auth, PASSWORD));
+    client.send(&RconPacket::new(2, PacketType::ExecCommand, "cvarlist"));
+    client.send(&RconPacket::new(3, PacketType::ResponseValue, ""));
+    client.stream.shutdown(Shutdown::Write).unwrap();
+
+    let auth = client.recv(&mut server, 2);
+    assert_eq!(auth[1], RconPacket::new(1, PacketType::AuthResponse, ""));
+
+    let mut output = String::new();
+    loop {
+        let packet = client.recv(&mut server, 1).remove(0);
+        if packet.id == 3 {
+            break;
+        }
+        output += &packet.body;
+    }
+    assert_eq!(output, format!("] \"cvarlist\"\n{}", line.repeat(400)));
+    assert_eq!(client.recv(&mut server, 1)[0].id, 3);
+
+    client.assert_disconnected(&mut server);
+}
+
+#[test]
+fn clients_are_reaped_when_they_hang_up() {
+    let mut server = TestServer::start();
+    let clients = (0..4).map(|_| server.login()).collect::<Vec<_>>();
+
+    assert_eq!(server.server.connection_count(), 4);
+
+    drop(clients);
+    for _ in 0..100 {
+        server.frame();
+        if server.server.connection_count() == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    assert_eq!(server.server.connection_count(), 0);
+}
+
+#[test]
+fn several_clients_at_once() {
+    let mut server = TestServer::start_with(|executor| {
+        (0..8).fold(executor, |executor, i| {
+            executor.with_response(format!("echo {i}"), [format!("{i}\n")])
+        })
+    });
+    // every client comes from localhost
+    server.server.set_limits(RconLimits {
+        max_connections_per_ip: 8,
+        ..Default::default()
+    });
+    let mut clients = (0..8).map(|_| server.connect()).collect::<Vec<_>>();
+
+    for client in clients.iter_mut() {
+        client.send(&RconPacket::new(1, PacketType::Auth, PASSWORD));
+    }
+    for client in clients.iter_mut() {
+        let packets = client.recv(&mut server, 2);
+        assert_eq!(packets[1], RconPacket::new(1, PacketType::AuthResponse, ""));
+    }
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        client.send(&RconPacket::new(
+            i as i32,
+            PacketType::ExecCommand,
+            format!("echo {i}"),
+        ));
+    }
+    for (i, client) in clients.iter_mut().enumerate() {
+        assert_eq!(
+            client.recv(&mut server, 1),
+            [RconPacket::new(
+                i as i32,
+                PacketType::ResponseValue,
+                format!("] \"echo\" \"{i}\"\n{i}\n")
+            )]
+        );
+    }
+}
+
+#[test]
+fn every_account_can_log_in() {
+    let mut server = TestServer::start_with_accounts(team_accounts(), |executor| executor);
+
+    for login in ["cat:ownerpw", "mod:modpw", "statsbot:botpw"] {
+        assert!(server.connect().auth(&mut server, login));
+    }
+    // the name picks the account so another account's password doesn't work for it
+    assert!(!server.connect().auth(&mut server, "mod:ownerpw"));
+    assert!(!server.connect().auth(&mut server, "ownerpw"));
+    assert!(!server.connect().auth(&mut server, "dog:ownerpw"));
+}
+
+#[test]
+fn viewer_can_read_console_but_not_execute() {
+    let mut server = TestServer::start_with_accounts(team_accounts(), |executor| {
+        executor.with_response("status", ["ok\n"])
+    });
+    let mut client = server.connect();
+    assert!(client.auth(&mut server, "statsbot:botpw"));
+
+    server.console.send("hello\n".to_string()).unwrap();
+    server.frame();
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 2, "dumpconsole"),
+        "hello\ndumpconsole: that's all; continue with since 1\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 3, "status"),
+        "status isn't allowed for the viewer role\n"
+    );
+    assert!(server.executed.lock().is_empty());
+}
+
+#[test]
+fn moderator_is_limited_to_moderation_commands() {
+    let mut server = TestServer::start_with_accounts(team_accounts(), |executor| {
+        executor
+            .with_response("kick someone", ["kicked someone\n"])
+            .with_response("quit", ["bye\n"])
+    });
+    let mut client = server.connect();
+    assert!(client.auth(&mut server, "mod:modpw"));
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 2, "kick someone"),
+        "] \"kick\" \"someone\"\nkicked someone\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 3, "quit"),
+        "quit isn't allowed for the moderator role\n"
+    );
+    assert_eq!(*server.executed.lock(), ["kick someone"]);
+
+    // the plugin's own commands go through the rules too
+    assert_eq!(
+        client.exec_to_string(&mut server, 4, "dumpconsole last 10"),
+        "dumpconsole last 10 isn't allowed for the moderator role\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 5, "rcon_subscribe"),
+        "rcon_subscribe isn't allowed for the moderator role\n"
+    );
+}
+
+#[test]
+fn viewers_can_be_kept_from_the_console() {
+    let mut server = TestServer::start_with_accounts(team_accounts(), |executor| executor);
+    let mut permissions = Permissions::default();
+    permissions.set_rules(
+        Role::Viewer,
+        RoleRules {
+            allow: vec![CommandRule::new("dumpconsole")],
+            deny: vec!["dumpconsole since .*".parse().unwrap()],
+        },
+    );
+    server.server.set_permissions(permissions);
+    let mut client = server.connect();
+    assert!(client.auth(&mut server, "statsbot:botpw"));
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 1, "rcon_subscribe warning"),
+        "rcon_subscribe warning isn't allowed for the viewer role\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 2, "dumpconsole since 0"),
+        "dumpconsole since 0 isn't allowed for the viewer role\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 3, "dumpconsole"),
+        "dumpconsole: that's all; continue with since 0\n"
+    );
+}
+
+#[test]
+fn deny_rules_match_arguments() {
+    let mut server = TestServer::start_with_accounts(team_accounts(), |executor| {
+        executor.with_response("sv_cheats 0", ["sv_cheats = 0\n"])
+    });
+    let mut permissions = Permissions::default();
+    permissions.set_rules(
+        Role::Owner,
+        RoleRules {
+            allow: vec![CommandRule::new("*")],
+            deny: vec![
+                "sv_cheats [^0].*".parse().unwrap(),
+                "kick cat".parse().unwrap(),
+            ],
+        },
+    );
+    server.server.set_permissions(permissions);
+
+    let mut client = server.connect();
+    assert!(client.auth(&mut server, "cat:ownerpw"));
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 1, "kick CAT"),
+        "kick CAT isn't allowed for the owner role\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 2, "SV_CHEATS 1"),
+        "SV_CHEATS 1 isn't allowed for the owner role\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 3, "sv_cheats 0"),
+        "] \"sv_cheats\" \"0\"\nsv_cheats = 0\n"
+    );
+}
+
+#[test]
+fn chained_commands_are_checked_separately() {
+    let mut server = TestServer::start_with_accounts(team_accounts(), |executor| {
+        executor
+            .with_response("kick x", ["kicked x\n"])
+            .with_response("say \"hi; there\"", ["hi; there\n"])
+    });
+    let mut client = server.connect();
+    assert!(client.auth(&mut server, "mod:modpw"));
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 2, "kick x; quit"),
+        "quit isn't allowed for the moderator role\n"
+    );
+    assert!(server.executed.lock().is_empty());
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 3, "kick x;say \"hi; there\""),
+        "] \"kick\" \"x\"\nkicked x\n] \"say\" \"hi; there\"\nhi; there\n"
+    );
+    assert_eq!(*server.executed.lock(), ["kick x", "say \"hi; there\""]);
+}
+
+#[test]
+fn failed_auth_delays_the_next_attempt() {
+    let mut server = TestServer::start();
+    server.server.set_auth_policy(quick_auth_policy());
+    let mut client = server.connect();
+
+    assert!(!client.auth(&mut server, "wrong"));
+    let retried_at = Instant::now();
+    assert!(client.auth(&mut server, PASSWORD));
+
+    // the delay starts at the frame that saw the failure, a few frames before the reply gets here
+    assert!(retried_at.elapsed() >= Duration::from_millis(80));
+}
+
+#[test]
+fn repeated_auth_failures_disconnect_then_ban() {
+    let mut server = TestServer::start();
+    server.server.set_auth_policy(quick_auth_policy());
+    // opened before the ban so it's only caught after accepting
+    let mut held = server.connect();
+
+    let mut client = server.connect();
+    assert!(!client.auth(&mut server, "wrong"));
+    assert!(!client.auth(&mut server, "wrong again"));
+    client.assert_disconnected(&mut server);
+
+    let mut client = server.connect();
+    assert!(!client.auth(&mut server, "still wrong"));
+    client.assert_disconnected(&mut server);
+
+    held.send(&RconPacket::new(1, PacketType::Auth, PASSWORD));
+    held.assert_disconnected(&mut server);
+
+    server.connect().assert_disconnected(&mut server);
+    assert_eq!(server.server.connection_count(), 0);
+}
+
+#[test]
+fn ip_filter_refuses_peers_at_accept() {
+    let mut server = TestServer::start();
+    server.server.set_ip_filter(IpFilter {
+        allow: parse_cidr_list("127.0.0.0/8").unwrap(),
+        deny: parse_cidr_list("127.0.0.1").unwrap(),
+    });
+
+    server.connect().assert_disconnected(&mut server);
+    assert_eq!(server.server.connection_count(), 0);
+
+    server.server.set_ip_filter(IpFilter {
+        allow: parse_cidr_list("10.0.0.0/8, ::1").unwrap(),
+        deny: Vec::new(),
+    });
+    server.connect().assert_disconnected(&mut server);
+
+    server.server.set_ip_filter(IpFilter {
+        allow: parse_cidr_list("127.0.0.0/8").unwrap(),
+        deny: Vec::new(),
+    });
+    let mut client = server.login();
+    assert_eq!(
+        client.exec_to_string(&mut server, 1, "status"),
+        "] \"status\"\nUnknown command \"status\"\n"
+    );
+}
+
+#[test]
+fn connections_past_the_limits_are_refused() {
+    let mut server = TestServer::start();
+    server.server.set_limits(RconLimits {
+        max_connections: 2,
+        ..Default::default()
+    });
+
+    let mut first = server.login();
+    let _second = server.login();
+    server.connect().assert_disconnected(&mut server);
+    assert_eq!(server.server.connection_count(), 2);
+
+    server.server.set_limits(RconLimits {
+        max_connections_per_ip: 1,
+        ..Default::default()
+    });
+    server.connect().assert_disconnected(&mut server);
+
+    // the sessions that got in before are left alone
+    assert_eq!(
+        first.exec_to_string(&mut server, 1, "status"),
+        "] \"status\"\nUnknown command \"status\"\n"
+    );
+}
+
+#[test]
+fn sessions_time_out() {
+    let mut server = TestServer::start();
+    server.server.set_limits(RconLimits {
+        auth_timeout: Duration::from_millis(100),
+        idle_timeout: Duration::from_millis(200),
+        ..Default::default()
+    });
+
+    let connected_at = Instant::now();
+    server.connect().assert_disconnected(&mut server);
+    assert!(connected_at.elapsed() >= Duration::from_millis(100));
+
+    let mut client = server.login();
+    std::thread::sleep(Duration::from_millis(120));
+    client.exec(&mut server, 1, "status");
+    std::thread::sleep(Duration::from_millis(120));
+    client.exec(&mut server, 2, "status");
+
+    let idle_since = Instant::now();
+    client.assert_disconnected(&mut server);
+    assert!(idle_since.elapsed() >= Duration::from_millis(150));
+}
+
+/// cheap hashes of `ownerpw` and `modpw`
+const OWNERPW_ARGON2: &str =
+    "$argon2id$v=19$m=256,t=1,p=1$cjJyY29uLXRlc3Qtc2FsdA$gxSfwiWXj4bbvnvhnPcuPpWgdAN/0x1pAeljAGQm3EQ";
+const MODPW_PBKDF2: &str =
+    "$pbkdf2-sha256$i=1000,l=32$cjJyY29uLXRlc3Qtc2FsdA$g/cALJHclzExxPxZfd3cj8sTpAjtAPav/9xmeO6svMA";
+
+#[test]
+fn hashed_accounts_can_log_in() {
+    let accounts = Accounts::new(vec![
+        Account::new(
+            "cat",
+            OWNERPW_ARGON2.parse::<Password>().unwrap(),
+            Role::Owner,
+        ),
+        Account::new(
+            "mod",
+            MODPW_PBKDF2.parse::<Password>().unwrap(),
+            Role::Moderator,
+        ),
+    ])
+    .unwrap();
+    let mut server = TestServer::start_with_accounts(accounts, |executor| executor);
+
+    let mut client = server.connect();
+    assert!(!client.auth(&mut server, &format!("cat:{OWNERPW_ARGON2}")));
+    assert!(client.auth(&mut server, "cat:ownerpw"));
+    assert!(server.connect().auth(&mut server, "mod:modpw"));
+}
+
+#[test]
+fn subscribers_get_console_lines_as_they_come() {
+    let mut server = TestServer::start_with(|executor| executor.with_response("status", ["ok\n"]));
+    let mut watcher = server.login();
+    let mut admin = server.login();
+
+    assert_eq!(
+        watcher.exec_to_string(&mut server, 7, "rcon_subscribe"),
+        "subscribed to console lines debug and up\n"
+    );
+
+    server
+        .console
+        .send("[info] cat joined\n".to_string())
+        .unwrap();
+    assert_eq!(
+        watcher.recv(&mut server, 1),
+        [RconPacket::new(
+            7,
+            PacketType::ResponseValue,
+            "[info] cat joined\n"
+        )]
+    );
+
+    // other sessions' command output is part of the console too
+    admin.exec(&mut server, 1, "status");
+    assert_eq!(
+        watcher.recv(&mut server, 1),
+        [RconPacket::new(7, PacketType::ResponseValue, "ok\n")]
+    );
+
+    assert_eq!(
+        watcher.exec_to_string(&mut server, 8, "rcon_unsubscribe"),
+        "unsubscribed from the console\n"
+    );
+    server
+        .console
+        .send("[info] cat left\n".to_string())
+        .unwrap();
+    assert_eq!(
+        watcher.exec_to_string(&mut server, 9, "rcon_unsubscribe"),
+        "wasn't subscribed to the console\n"
+    );
+}
+
+#[test]
+fn subscribers_dont_time_out_while_listening() {
+    let mut server = TestServer::start();
+    server.server.set_limits(RconLimits {
+        idle_timeout: Duration::from_millis(100),
+        ..Default::default()
+    });
+    let mut watcher = server.login();
+    let mut idler = server.login();
+
+    watcher.exec(&mut server, 1, "rcon_subscribe");
+    idler.assert_disconnected(&mut server);
+
+    std::thread::sleep(Duration::from_millis(150));
+    server.console.send("still here\n".to_string()).unwrap();
+    assert_eq!(
+        watcher.recv(&mut server, 1),
+        [RconPacket::new(
+            1,
+            PacketType::ResponseValue,
+            "still here\n"
+        )]
+    );
+    assert_eq!(server.server.connection_count(), 1);
+
+    // unsubscribing makes it an ordinary session again
+    watcher.exec(&mut server, 2, "rcon_unsubscribe");
+    watcher.assert_disconnected(&mut server);
+}
+
+#[test]
+fn subscriptions_filter_by_severity_and_pattern() {
+    assert_eq!(
+        Severity::of("[22:01:13] [NORTHSTAR] [warning] oh no"),
+        Severity::Warning
+    );
+    assert_eq!(Severity::of("Error: script died"), Severity::Error);
+    assert_eq!(Severity::of("cat killed dog"), Severity::Info);
+
+    let mut server = TestServer::start();
+    let mut client = server.login();
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 3, "rcon_subscribe warn kick(ed)? \\w+"),
+        "subscribed to console lines warning and up matching kick(ed)? \\w+\n"
+    );
+    for line in [
+        "[info] kicked cat\n",
+        "[warning] kick failed\n",
+        "[error] crashed\n",
+        "[error] kicked dog\n",
+    ] {
+        server.console.send(line.to_string()).unwrap();
+    }
+    assert_eq!(
+        client
+            .recv(&mut server, 2)
+            .into_iter()
+            .map(|packet| packet.body.into_owned())
+            .collect::<Vec<_>>(),
+        ["[warning] kick failed\n", "[error] kicked dog\n"]
+    );
+
+    assert!(client
+        .exec_to_string(&mut server, 4, "rcon_subscribe error (")
+        .starts_with("couldn't subscribe : regex parse error"));
+}
+
+#[test]
+fn subscribing_needs_auth() {
+    let mut server = TestServer::start();
+    let mut client = server.connect();
+
+    client.send(&RconPacket::new(
+        1,
+        PacketType::ExecCommand,
+        "rcon_subscribe",
+    ));
+    client.assert_disconnected(&mut server);
+}
+
+#[test]
+fn command_output_leaves_out_background_lines() {
+    let mut server = TestServer::start_with(|executor| {
+        executor
+            .with_response("status", ["hostname: test server\n"])
+            .with_response("say hi", ["hi\n"])
+    });
+    let mut cat = server.login();
+    let mut dog = server.login();
+
+    server
+        .console
+        .send("[info] map loaded\n".to_string())
+        .unwrap();
+    cat.send(&RconPacket::new(1, PacketType::ExecCommand, "status"));
+    dog.send(&RconPacket::new(2, PacketType::ExecCommand, "say hi"));
+
+    assert_eq!(
+        cat.recv(&mut server, 1)[0].body,
+        "] \"status\"\nhostname: test server\n"
+    );
+    assert_eq!(dog.recv(&mut server, 1)[0].body, "] \"say\" \"hi\"\nhi\n");
+    assert_eq!(*server.executed.lock(), ["status", "say hi"]);
+
+    // the background line is still in the console but the sentinels aren't
+    let dump = cat.exec_to_string(&mut server, 3, "dumpconsole");
+    assert_eq!(
+        dump,
+        "[info] map loaded\nhostname: test server\nhi\ndumpconsole: that's all; continue with since 3\n"
+    );
+}
+
+#[test]
+fn late_output_is_waited_for() {
+    let mut server = TestServer::start_with(|executor| {
+        executor.with_late_response(
+            "changelevel mp_glitch",
+            ["loading mp_glitch\n"],
+            Duration::from_millis(50),
+        )
+    });
+    server.server.set_limits(RconLimits {
+        response_frames: u32::MAX,
+        ..Default::default()
+    });
+    let mut client = server.login();
+
+    // the mirrored packet only comes back after the late output, under the request's own id
+    assert_eq!(
+        client.exec_to_string(&mut server, 7, "changelevel mp_glitch"),
+        "] \"changelevel\" \"mp_glitch\"\nloading mp_glitch\n"
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 8, "dumpconsole"),
+        "loading mp_glitch\ndumpconsole: that's all; continue with since 1\n"
+    );
+}
+
+#[test]
+fn output_after_the_end_sentinel_is_collected() {
+    let mut server = TestServer::start_with(|executor| {
+        executor.with_response("changelevel mp_glitch", ["changing level\n"])
+    });
+    server.server.set_limits(RconLimits {
+        response_frames: u32::MAX,
+        response_timeout: Duration::from_secs(60),
+        response_quiet_frames: 3,
+        ..Default::default()
+    });
+    let mut client = server.login();
+
+    client.send(&RconPacket::new(
+        7,
+        PacketType::ExecCommand,
+        "changelevel mp_glitch",
+    ));
+    client.send(&RconPacket::new(8, PacketType::ResponseValue, ""));
+    while server.executed.lock().is_empty() {
+        server.frame();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    // the command and both its sentinels are done by now, like a map change that loads over the next frames
+    server.frame();
+    server
+        .console
+        .send("loading mp_glitch\n".to_string())
+        .unwrap();
+
+    let packets = client.recv(&mut server, 3);
+    assert_eq!(
+        packets[0],
+        RconPacket::new(
+            7,
+            PacketType::ResponseValue,
+            "] \"changelevel\" \"mp_glitch\"\nchanging level\nloading mp_glitch\n"
+        )
+    );
+    assert_eq!(
+        packets[1],
+        RconPacket::new(8, PacketType::ResponseValue, "")
+    );
+}
+
+#[test]
+fn output_after_the_end_sentinel_is_never_shared() {
+    let mut server = TestServer::start_with_accounts(team_accounts(), |executor| {
+        executor
+            .with_response("status", ["hostname: test server\n"])
+            .with_response("say hi", ["hi\n"])
+    });
+    let mut cat = server.connect();
+    assert!(cat.auth(&mut server, "cat:ownerpw"));
+    let mut dog = server.connect();
+    assert!(dog.auth(&mut server, "cat:ownerpw"));
+
+    // with two commands waiting nobody can tell whose it is
+    cat.send(&RconPacket::new(1, PacketType::ExecCommand, "status"));
+    dog.send(&RconPacket::new(2, PacketType::ExecCommand, "say hi"));
+    while server.executed.lock().len() < 2 {
+        server.frame();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    server
+        .console
+        .send("player joined: secret\n".to_string())
+        .unwrap();
+
+    assert_eq!(
+        cat.recv(&mut server, 1)[0].body,
+        "] \"status\"\nhostname: test server\n"
+    );
+    assert_eq!(dog.recv(&mut server, 1)[0].body, "] \"say\" \"hi\"\nhi\n");
+
+    // and a role that can't read the console doesn't get it either
+    let mut moderator = server.connect();
+    assert!(moderator.auth(&mut server, "mod:modpw"));
+    moderator.send(&RconPacket::new(3, PacketType::ExecCommand, "status"));
+    while server.executed.lock().len() < 3 {
+        server.frame();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    server
+        .console
+        .send("player joined: secret\n".to_string())
+        .unwrap();
+
+    assert_eq!(
+        moderator.recv(&mut server, 1)[0].body,
+        "] \"status\"\nhostname: test server\n"
+    );
+}
+
+#[test]
+fn waiting_for_output_gives_up() {
+    let mut server = TestServer::start_with(|executor| {
+        executor.with_late_response(
+            "changelevel mp_glitch",
+            ["loading mp_glitch\n"],
+            Duration::from_millis(300),
+        )
+    });
+    server.server.set_limits(RconLimits {
+        response_frames: u32::MAX,
+        response_timeout: Duration::from_millis(20),
+        ..Default::default()
+    });
+    let mut client = server.login();
+
+    let started = Instant::now();
+    assert_eq!(
+        client.exec_to_string(&mut server, 1, "changelevel mp_glitch"),
+        "] \"changelevel\" \"mp_glitch\"\n"
+    );
+    assert!(started.elapsed() < Duration::from_millis(300));
+
+    // the output still makes it into the console once it shows up, without the sentinels
+    std::thread::sleep(Duration::from_millis(350));
+    assert_eq!(
+        client.exec_to_string(&mut server, 2, "dumpconsole"),
+        "loading mp_glitch\ndumpconsole: that's all; continue with since 1\n"
+    );
+
+    // running out of frames ends the wait as well
+    let mut server = TestServer::start_with(|executor| {
+        executor.with_late_response(
+            "status",
+            ["hostname: test server\n"],
+            Duration::from_secs(1),
+        )
+    });
+    server.server.set_limits(RconLimits {
+        response_frames: 0,
+        response_timeout: Duration::from_secs(60),
+        ..Default::default()
+    });
+    let mut client = server.login();
+    assert_eq!(
+        client.exec_to_string(&mut server, 3, "status"),
+        "] \"status\"\n"
+    );
+}
+
+#[test]
+fn limits_too_small_for_a_packet_are_raised() {
+    let mut server = TestServer::start_with(|executor| executor.with_response("status", ["ok\n"]));
+    server.server.set_limits(RconLimits {
+        max_packet_size: 0,
+        max_write_buffer: 0,
+        ..Default::default()
+    });
+    let mut client = server.login();
+
+    assert_eq!(
+        client.exec_to_string(&mut server, 1, "status"),
+        "] \"status\"\nok\n"
+    );
+}
+
+#[test]
+fn dumps_fit_in_the_write_buffer() {
+    let mut server = TestServer::start();
+    server.server.set_limits(RconLimits {
+        max_write_buffer: 16 * 1024,
+        ..Default::default()
+    });
+    let mut client = server.login();
+
+    for i in 0..100 {
+        server
+            .console
+            .send(format!("{i} {}\n", "x".repeat(1000)))
+            .unwrap();
+    }
+    server.frame();
+
+    let dump = client.exec_to_string(&mut server, 1, "dumpconsole since 0");
+    assert!(dump.len() <= 8 * 1024 + 1100);
+    assert!(dump.starts_with("0 x"));
+    assert!(dump
+        .lines()
+        .last()
+        .unwrap()
+        .starts_with("dumpconsole: there's more; continue with page "));
+}
+
+#[test]
+fn dumpconsole_takes_options_and_keeps_lines_whole() {
+    let mut server = TestServer::start();
+    let mut client = server.login();
+
+    let line = "x".repeat(1000);
+    for i in 0..2000 {
+        server.console.send(format!("{i} {line}\n")).unwrap();
+    }
+    server.frame();
+
+    // pages stop well short of 2000 lines this long so it takes following the tokens
+    let mut dumped = Vec::new();
+    let mut command = "dumpconsole last 2000 limit 2000".to_string();
+    for id in 1.. {
+        let packets = client.exec(&mut server, id, &command);
+        assert!(packets.iter().all(|packet| packet.body.ends_with('\n')));
+
+        let dump = packets
+            .into_iter()
+            .map(|packet| packet.body.into_owned())
+            .collect::<String>();
+        let mut lines = dump.lines().map(str::to_string).collect::<Vec<_>>();
+
+        match lines
+            .last()
+            .and_then(|last| last.strip_prefix("dumpconsole: there's more; continue with page "))
+        {
+            Some(page) => {
+                command = format!("dumpconsole last 2000 limit 2000 page {page}");
+                _ = lines.pop();
+                dumped.extend(lines);
+            }
+            None => {
+                assert_eq!(
+                    lines.pop().unwrap(),
+                    "dumpconsole: that's all; continue with since 2000"
+                );
+                dumped.extend(lines);
+                break;
+            }
+        }
+    }
+    assert_eq!(
+        dumped,
+        (0..2000).map(|i| format!("{i} {line}")).collect::<Vec<_>>()
+    );
+
+    assert_eq!(
+        client.exec_to_string(
+            &mut server,
+            3,
+            "dumpconsole include \"^(7|12) \" exclude ^7"
+        ),
+        format!("12 {line}\ndumpconsole: that's all; continue with since 2000\n")
+    );
+    assert_eq!(
+        client.exec_to_string(&mut server, 4, "dumpconsole last"),
+        "couldn't dump the console : last needs a value\n"
+    );
+}
+
+#[test]
+fn polling_with_since_misses_nothing() {
+    let mut server = TestServer::start();
+    let mut client = server.login();
+
+    let mut seen = Vec::new();
+    let mut command = "dumpconsole".to_string();
+    for (id, batch) in (1..).zip([0..3, 3..3, 3..10, 10..11]) {
+        for i in batch {
+            server.console.send(format!("line {i}\n")).unwrap();
+        }
+        server.frame();
+
+        let dump = client.exec_to_string(&mut server, id, &command);
+        let mut lines = dump.lines().collect::<Vec<_>>();
+        let since = lines
+            .pop()
+            .and_then(|last| last.strip_prefix("dumpconsole: that's all; continue with since "))
+            .expect("every dump says where to continue");
+
+        seen.extend(lines.into_iter().map(str::to_string));
+        command = format!("dumpconsole since {since}");
+    }
+
+    assert_eq!(
+        seen,
+        (0..11).map(|i| format!("line {i}")).collect::<Vec<_>>()
+    );
+}